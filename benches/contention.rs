@@ -0,0 +1,91 @@
+//! Measures the CPU time burnt by threads contending for an inventory.
+//!
+//! Eight threads track and drop 50_000 objects each, and the wall-clock
+//! and CPU times of the whole run are reported. The spin locks used
+//! without `std` are measured with:
+//!
+//! ```text
+//! cargo bench --no-default-features --features spin --bench contention
+//! ```
+//!
+//! The CPU time is read from `/proc/self/stat`, and is only reported on
+//! Linux.
+//!
+//! The inventory takes its locks without any backoff of its own: measured
+//! with this benchmark, an exponential backoff of `spin_loop` hints did
+//! not reduce the CPU time burnt under contention, neither with the `std`
+//! locks nor with the spin locks, and yielding the thread requires `std`.
+
+extern crate census;
+
+use census::Inventory;
+use std::fs;
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const THREADS: usize = 8;
+const OBJECTS_PER_THREAD: usize = 50_000;
+const ROUNDS: usize = 10;
+
+/// Clock ticks per second of the times of `/proc/self/stat`.
+const USER_HZ: u64 = 100;
+
+/// Returns the user and system CPU time consumed by the process so far.
+fn cpu_time() -> Option<Duration> {
+    let stat = fs::read_to_string("/proc/self/stat").ok()?;
+    // The command name may contain spaces, but is enclosed in parentheses.
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 2..].split(' ').collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(Duration::from_millis((utime + stime) * 1_000 / USER_HZ))
+}
+
+fn contended_round() {
+    let inventory = Inventory::new();
+    let start = Arc::new(Barrier::new(THREADS));
+    let handles: Vec<_> = (0..THREADS)
+        .map(|thread_id| {
+            let inventory = inventory.clone();
+            let start = start.clone();
+            thread::spawn(move || {
+                start.wait();
+                for i in 0..OBJECTS_PER_THREAD {
+                    let obj = inventory.track(thread_id * OBJECTS_PER_THREAD + i);
+                    if i % 2 == 0 {
+                        inventory.len();
+                    }
+                    drop(obj);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert!(inventory.is_empty());
+}
+
+fn main() {
+    let mut wall_times = Vec::new();
+    let mut cpu_times = Vec::new();
+    for _ in 0..ROUNDS {
+        let cpu_before = cpu_time();
+        let start = Instant::now();
+        contended_round();
+        wall_times.push(start.elapsed());
+        if let (Some(before), Some(after)) = (cpu_before, cpu_time()) {
+            cpu_times.push(after - before);
+        }
+    }
+    wall_times.sort();
+    cpu_times.sort();
+    println!(
+        "{} threads x {} objects, median of {} rounds",
+        THREADS, OBJECTS_PER_THREAD, ROUNDS
+    );
+    println!("wall time: {:?}", wall_times[ROUNDS / 2]);
+    if let Some(cpu) = cpu_times.get(ROUNDS / 2) {
+        println!("cpu time:  {:?}", cpu);
+    }
+}
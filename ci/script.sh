@@ -11,6 +11,7 @@ main() {
     fi
 
     cross test --target $TARGET
+    cross test --target $TARGET --no-default-features --features spin
     # cross test --target $TARGET --release
 
     # cross run --target $TARGET
@@ -18,24 +18,232 @@
 //! let living_instances: Vec<TrackedObject<String>> = inventory.list();
 //! assert_eq!(living_instances.len(), 2);
 //! ```
+//!
+//! # Memory layout
+//!
+//! By default, each tracked object lives in its own reference counted
+//! allocation, and the inventory only keeps a weak reference to it.
+//! This is what makes it possible for a `TrackedObject<T>` to deref to
+//! its `T` without locking the inventory: the address of the object is
+//! stable for as long as one of its handles is alive.
+//!
+//! Tracking millions of small objects that way fragments the heap.
+//! `InventoryBuilder::slab` stores the objects in a slab instead. The slab
+//! is allocated by pages which never move as it grows, so that the objects
+//! keep a stable address, and the inventory keeps the index and generation
+//! of the slot of each object instead of a weak reference.
+//!
+//! If the objects are only tracked to be counted, `CounterInventory`
+//! does not allocate anything per object.
+//!
+//! # `no_std`
+//!
+//! The `std` feature is enabled by default. Without it, census only
+//! requires `alloc`, and takes its locks from the `spin` crate, which
+//! its `spin` feature enables. Tracking, listing, counting, quotas,
+//! children and the slab storage remain available, but everything that relies on a clock, on parking threads or on
+//! channels requires `std`: the `wait_*` methods, the creation time and
+//! age of the objects, `collect_on_drain`, the wait strategies, key
+//! quotas, `count_by`, `leaderboard`, `lineage`, `write_dump`,
+//! `TrackedMut`, and the `rayon`, `serde`, `tokio` and `tracing`
+//! integrations, which enable `std` themselves.
+//!
+//! # Tracking trait objects
+//!
+//! Tracked objects are owned by the inventory, so that they can be handed
+//! back, e.g. by `reclaim`, and `T` has to be sized. Objects of different
+//! types can still be tracked in the same inventory by boxing them:
+//!
+//! ```rust
+//! use census::Inventory;
+//!
+//! trait Connection {
+//!     fn peer(&self) -> String;
+//! }
+//!
+//! struct Tcp(u16);
+//! struct Unix(&'static str);
+//!
+//! impl Connection for Tcp {
+//!     fn peer(&self) -> String {
+//!         format!("port {}", self.0)
+//!     }
+//! }
+//!
+//! impl Connection for Unix {
+//!     fn peer(&self) -> String {
+//!         self.0.to_string()
+//!     }
+//! }
+//!
+//! let inventory: Inventory<Box<dyn Connection>> = Inventory::new();
+//! let _tcp = inventory.track(Box::new(Tcp(80)));
+//! let _unix = inventory.track(Box::new(Unix("/tmp/socket")));
+//! let mut peers: Vec<String> = inventory.list().iter().map(|conn| conn.peer()).collect();
+//! peers.sort();
+//! assert_eq!(peers, vec!["/tmp/socket", "port 80"]);
+//! ```
 
-use std::borrow::Borrow;
-use std::fmt;
-use std::ops::Deref;
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+#[cfg(not(any(feature = "std", feature = "spin")))]
+compile_error!("census requires either its `std` feature or its `spin` feature.");
 
-use std::fmt::{Error, Formatter};
+#[cfg(feature = "std")]
+extern crate core;
+#[macro_use]
+extern crate alloc;
+
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+#[cfg(all(feature = "spin", not(feature = "std")))]
+extern crate spin;
+
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
+#[cfg(loom)]
+extern crate loom;
+
+mod slab;
+mod sync;
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BinaryHeap};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Reverse;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::mem::{self, ManuallyDrop};
+use core::ops::{ControlFlow, Deref};
+#[cfg(feature = "backtrace")]
+use core::panic::Location;
+use core::ptr;
+#[cfg(feature = "std")]
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::sync::mpsc::{self, Receiver, Sender};
+#[cfg(feature = "std")]
+use std::sync::PoisonError;
+#[cfg(feature = "std")]
+use std::thread::{self, JoinHandle, Thread};
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+#[cfg(feature = "std")]
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+
+use slab::{Handle, Slab, WeakHandle};
+use sync::atomic::{AtomicU64, AtomicUsize};
+#[cfg(feature = "std")]
+use sync::Condvar;
+use sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use core::fmt::{Error, Formatter};
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
 
 struct Items<T> {
     alive_count: usize,
-    items: Vec<Weak<InnerTrackedObject<T>>>,
+    // Number of living objects tracked directly in this inventory, rather than
+    // in one of its children. The maximum and the capacity alarm apply to it.
+    direct_alive_count: usize,
+    peak: usize,
+    total_tracked: u64,
+    total_dropped: u64,
+    version: u64,
+    gc_passes: u64,
+    quota: Option<Box<dyn Quota<T> + Send + Sync>>,
+    max_alive: Option<usize>,
+    capacity_alarm: Option<CapacityAlarm>,
+    #[cfg(feature = "std")]
+    wait_strategy: WaitStrategy,
+    growth_policy: GrowthPolicy,
+    // Garbage collection runs once this ratio of the internal vector is dead.
+    gc_dead_ratio: f64,
+    // Number of threads waiting on the condition variable.
+    #[cfg(feature = "std")]
+    condvar_waiters: usize,
+    // Threads waiting on the inventory, with the `Park` wait strategy.
+    #[cfg(feature = "std")]
+    parked_threads: Vec<Thread>,
+    #[cfg(feature = "tokio")]
+    count_sender: Option<tokio::sync::watch::Sender<usize>>,
+    // Last snapshot returned by `list_cached`, with the version it was taken at.
+    // The cache only holds a weak reference, so that it does not pin the objects.
+    list_cache: Option<(u64, Weak<CachedList<T>>)>,
+    list_order: ListOrder,
+    observer: Option<Observer<T>>,
+    // Channel receiving the values of dying objects, set by `collect_on_drain`.
+    #[cfg(feature = "std")]
+    drain_sender: Option<Sender<T>>,
+    // Checks the consistency of the accounting after every change, in debug builds.
+    strict: bool,
+    items: Vec<WeakHandle<InnerTrackedObject<T>>>,
+}
+
+type CachedList<T> = Vec<TrackedObject<T>>;
+
+/// Channel receiving the values of dying objects, see `Inventory::collect_on_drain`.
+#[cfg(feature = "std")]
+type DrainSender<T> = Sender<T>;
+
+/// Without `std`, the values of dying objects are always dropped:
+/// a `DrainSender` is never created.
+#[cfg(not(feature = "std"))]
+struct DrainSender<T>(core::convert::Infallible, core::marker::PhantomData<T>);
+
+#[cfg(not(feature = "std"))]
+impl<T> DrainSender<T> {
+    fn send(&self, _value: T) -> Result<(), T> {
+        match self.0 {}
+    }
 }
 
 impl<T> Default for Items<T> {
     fn default() -> Self {
         Items {
             alive_count: 0,
+            direct_alive_count: 0,
+            peak: 0,
+            total_tracked: 0,
+            total_dropped: 0,
+            version: 0,
+            gc_passes: 0,
+            quota: None,
+            max_alive: None,
+            capacity_alarm: None,
+            #[cfg(feature = "std")]
+            wait_strategy: WaitStrategy::default(),
+            growth_policy: GrowthPolicy::default(),
+            gc_dead_ratio: DEFAULT_GC_DEAD_RATIO,
+            #[cfg(feature = "std")]
+            condvar_waiters: 0,
+            #[cfg(feature = "std")]
+            parked_threads: Vec::new(),
+            #[cfg(feature = "tokio")]
+            count_sender: None,
+            list_cache: None,
+            list_order: ListOrder::default(),
+            observer: None,
+            #[cfg(feature = "std")]
+            drain_sender: None,
+            strict: false,
             items: Vec::new(),
         }
     }
@@ -44,26 +252,132 @@ impl<T> Default for Items<T> {
 impl<T> Items<T> {
     fn record_birth(&mut self) {
         self.alive_count += 1;
+        self.total_tracked += 1;
+        self.version += 1;
+        if self.alive_count > self.peak {
+            self.peak = self.alive_count;
+        }
     }
 
-    fn record_death(&mut self) {
+    fn record_death(&mut self, item: &T) {
+        self.direct_alive_count -= 1;
+        self.record_descendant_death();
+        if let Some(quota) = self.quota.as_mut() {
+            quota.release(item);
+        }
+    }
+
+    /// Returns the channel the values of dying objects should be sent to, if any.
+    ///
+    /// Once the inventory is closed and empty, no object can die anymore:
+    /// the channel is released, so that its receiver gets disconnected.
+    #[cfg(feature = "std")]
+    fn drain_sender(&mut self, closed: bool) -> Option<DrainSender<T>> {
+        if closed && self.alive_count == 0 {
+            self.drain_sender.take()
+        } else {
+            self.drain_sender.clone()
+        }
+    }
+
+    /// Without `std`, there is no channel to send the values of dying objects to.
+    #[cfg(not(feature = "std"))]
+    fn drain_sender(&mut self, _closed: bool) -> Option<DrainSender<T>> {
+        None
+    }
+
+    /// Records the death of an object listed in this inventory, whether it was
+    /// tracked in a child inventory or directly in this one, by `record_death`.
+    fn record_descendant_death(&mut self) {
         self.alive_count -= 1;
+        self.total_dropped += 1;
+        self.version += 1;
+    }
+
+    /// Returns true if `item` can be tracked without exceeding the quota,
+    /// in which case a slot is reserved for it.
+    fn admit(&mut self, item: &T) -> bool {
+        if let Some(max_alive) = self.max_alive {
+            if self.direct_alive_count >= max_alive {
+                return false;
+            }
+        }
+        match self.quota.as_mut() {
+            Some(quota) => quota.try_acquire(item),
+            None => true,
+        }
+    }
+
+    /// Returns the capacity alarm callback, with its arguments, if the births
+    /// since the count of objects tracked directly was `alive_before` made
+    /// the utilization cross the alarm ratio.
+    fn crossed_capacity_alarm(&self, alive_before: usize) -> Option<(AlarmCallback, usize, usize)> {
+        let max_alive = self.max_alive?;
+        let alarm = self.capacity_alarm.as_ref()?;
+        let threshold = alarm.ratio * max_alive as f64;
+        let alive = self.direct_alive_count;
+        if alive as f64 >= threshold && (alive_before as f64) < threshold {
+            Some((alarm.callback.clone(), alive, max_alive))
+        } else {
+            None
+        }
+    }
+
+    fn stats(&self) -> CensusStats {
+        CensusStats {
+            alive: self.alive_count,
+            peak: self.peak,
+            total_tracked: self.total_tracked,
+            total_dropped: self.total_dropped,
+            version: self.version,
+            capacity: self.items.capacity(),
+            gc_passes: self.gc_passes,
+        }
     }
 
-    fn len(&mut self) -> usize {
+    fn len(&self) -> usize {
         self.alive_count()
     }
 
-    fn list_arc(&mut self) -> Vec<TrackedObject<T>> {
+    fn list_arc(&self) -> Vec<TrackedObject<T>> {
+        let mut objects: Vec<TrackedObject<T>> = self.living_objects().collect();
+        if self.list_order == ListOrder::InsertionDesc {
+            objects.reverse();
+        }
+        objects
+    }
+
+    /// Returns the cached snapshot, if it is still alive and up to date.
+    ///
+    /// Upgrading the cache only when the version matches ensures we never
+    /// drop the last reference to a stale snapshot while holding the lock.
+    fn cached_list(&self) -> Option<Arc<CachedList<T>>> {
+        match self.list_cache {
+            Some((cached_version, ref snapshot)) if cached_version == self.version => {
+                snapshot.upgrade()
+            }
+            _ => None,
+        }
+    }
+
+    fn living_objects(&self) -> impl Iterator<Item = TrackedObject<T>> + '_ {
         self.items
             .iter()
             .flat_map(|weak| weak.upgrade())
             .map(|v| TrackedObject { inner: v })
-            .collect()
     }
 
     fn gc_if_needed(&mut self) {
-        if !self.should_gc() {
+        if self.should_gc() {
+            self.gc();
+        }
+    }
+
+    fn gc(&mut self) {
+        self.gc_passes += 1;
+        if self.list_order != ListOrder::Unspecified {
+            // Keep the weak references in insertion order.
+            self.items.retain(|weak| weak.strong_count() > 0);
             return;
         }
         let mut i = 0;
@@ -81,14 +395,204 @@ impl<T> Items<T> {
         self.alive_count
     }
 
+    /// Panics if there are more living objects than the recorded count.
+    ///
+    /// The recorded count can exceed the number of living objects: objects
+    /// being dropped by other threads are counted until their death is recorded.
+    fn check_consistency(&self) {
+        let living = self
+            .items
+            .iter()
+            .filter(|weak| weak.strong_count() > 0)
+            .count();
+        assert!(
+            living <= self.alive_count,
+            "Inconsistent inventory: {} living objects, but only {} recorded.",
+            living,
+            self.alive_count
+        );
+    }
+
     fn should_gc(&self) -> bool {
-        self.alive_count * 2 <= self.items.len()
+        let len = self.items.len();
+        let dead = len.saturating_sub(self.alive_count);
+        len > 0 && dead as f64 >= len as f64 * self.gc_dead_ratio
+    }
+}
+
+type Observer<T> = Arc<dyn Fn(CensusEvent<T>) + Send + Sync>;
+
+type AlarmCallback = Arc<dyn Fn(usize, usize) + Send + Sync>;
+
+struct CapacityAlarm {
+    ratio: f64,
+    callback: AlarmCallback,
+}
+
+/// A limit on the objects that can be alive at the same time in an inventory.
+trait Quota<T> {
+    /// Reserves a slot for `item`, or returns false if the quota is exceeded.
+    fn try_acquire(&mut self, item: &T) -> bool;
+
+    /// Releases the slot held by `item`.
+    fn release(&mut self, item: &T);
+}
+
+#[cfg(feature = "std")]
+struct KeyQuota<K, F> {
+    limit_per_key: usize,
+    key_fn: F,
+    counts: HashMap<K, usize>,
+}
+
+#[cfg(feature = "std")]
+impl<T, K: Eq + Hash, F: Fn(&T) -> K> Quota<T> for KeyQuota<K, F> {
+    fn try_acquire(&mut self, item: &T) -> bool {
+        let count = self.counts.entry((self.key_fn)(item)).or_insert(0);
+        if *count >= self.limit_per_key {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+
+    fn release(&mut self, item: &T) {
+        let key = (self.key_fn)(item);
+        let is_last = match self.counts.get_mut(&key) {
+            Some(count) => {
+                *count -= 1;
+                *count == 0
+            }
+            None => false,
+        };
+        if is_last {
+            self.counts.remove(&key);
+        }
+    }
+}
+
+/// How often a waiting thread checks its cancellation flag.
+#[cfg(feature = "std")]
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// How often `Inventory::wait_until_empty_with` calls its callback
+/// when the inventory does not change.
+#[cfg(feature = "std")]
+const WAIT_CALLBACK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of lines written by `Inventory::write_dump` between two flushes.
+#[cfg(feature = "std")]
+const DUMP_FLUSH_INTERVAL: usize = 1024;
+
+/// Ratio of dead references in the internal vector triggering a garbage collection.
+const DEFAULT_GC_DEAD_RATIO: f64 = 0.5;
+
+/// The outcome of `Inventory::wait_until_predicate_ex`.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WaitOutcome {
+    /// The predicate was satisfied.
+    Satisfied,
+    /// The timeout elapsed before the predicate was satisfied.
+    TimedOut,
+    /// The wait was cancelled before the predicate was satisfied.
+    Cancelled,
+    /// The inventory was closed before the predicate was satisfied.
+    Closed,
+}
+
+/// A consistent snapshot of the counters of an `Inventory`.
+///
+/// See `Inventory::stats`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CensusStats {
+    /// Number of objects currently alive.
+    pub alive: usize,
+    /// Highest number of objects ever alive at the same time.
+    pub peak: usize,
+    /// Number of objects tracked since the creation of the inventory.
+    pub total_tracked: u64,
+    /// Number of objects untracked since the creation of the inventory.
+    pub total_dropped: u64,
+    /// Counter incremented on every birth and death.
+    pub version: u64,
+    /// Capacity of the internal vector of weak references.
+    pub capacity: usize,
+    /// Number of garbage collection passes run on the internal vector.
+    pub gc_passes: u64,
+}
+
+/// The health of an `Inventory`, as returned by `Inventory::health`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Health {
+    /// Number of objects currently alive.
+    pub count: usize,
+    /// True if the inventory was closed.
+    pub closed: bool,
+    /// True if a thread panicked while holding the inventory lock.
+    ///
+    /// The inventory keeps working after such a panic, but the panic
+    /// may be the sign of a bug.
+    pub poisoned: bool,
+}
+
+impl Health {
+    /// Returns true if the inventory is neither closed nor poisoned.
+    pub fn is_healthy(&self) -> bool {
+        !self.closed && !self.poisoned
     }
 }
 
+/// A serializable report on the content of an inventory.
+///
+/// See `Inventory::to_report`.
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, Serialize)]
+pub struct CensusReport<T> {
+    /// Name of the inventory, if any.
+    pub name: Option<String>,
+    /// Number of objects alive.
+    pub alive: usize,
+    /// Highest number of objects ever alive at the same time.
+    pub peak: usize,
+    /// Number of objects tracked since the creation of the inventory.
+    pub total_tracked: u64,
+    /// The living objects.
+    pub objects: Vec<ObjectReport<T>>,
+}
+
+/// A serializable report on a living object.
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, Serialize)]
+pub struct ObjectReport<T> {
+    /// Id of the object.
+    pub id: u64,
+    /// Time elapsed since the object was tracked, in milliseconds.
+    pub age_ms: u64,
+    /// A clone of the object value.
+    pub value: T,
+}
+
 struct InnerInventory<T> {
-    items: Mutex<Items<T>>,
+    name: Option<String>,
+    // Read-only operations share the lock, so that listing the inventory
+    // from several threads does not serialize them.
+    items: RwLock<Items<T>>,
+    // Condition variables require a mutex. `waiters` is only locked by
+    // waiters and notifiers while holding the write lock on `items`.
+    #[cfg(feature = "std")]
+    waiters: Mutex<()>,
+    #[cfg(feature = "std")]
     condvar: Condvar,
+    // Mirror of `Items::alive_count`, readable without taking the lock.
+    alive: AtomicUsize,
+    closed: sync::atomic::AtomicBool,
+    next_id: AtomicU64,
+    // Number of deaths recorded, which is also the sequence number of the last death.
+    death_seq: AtomicU64,
+    parent: Option<Inventory<T>>,
+    // Storage of the objects, if they are not allocated separately.
+    slab: Option<Arc<Slab<InnerTrackedObject<T>>>>,
 }
 
 /// The `Inventory` register and keeps track of all of the objects alive.
@@ -96,337 +600,6059 @@ pub struct Inventory<T> {
     inner: Arc<InnerInventory<T>>,
 }
 
-impl<T> Default for Inventory<T> {
-    fn default() -> Self {
-        Inventory {
-            inner: Arc::new(InnerInventory {
-                items: Mutex::new(Items::default()),
-                condvar: Condvar::new(),
-            }),
-        }
-    }
+/// How threads blocked in the `wait_until_*` functions wait for the
+/// inventory to change.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub enum WaitStrategy {
+    /// Waiters block on a condition variable.
+    #[default]
+    Condvar,
+    /// Waiters register their thread handle and park.
+    /// Births and deaths unpark the registered threads.
+    Park,
 }
 
-impl<T> Clone for Inventory<T> {
-    fn clone(&self) -> Self {
-        Inventory {
-            inner: self.inner.clone(),
-        }
+/// The error returned by `Inventory::checked_track` when the inventory
+/// was closed. It hands back the object that could not be tracked.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct ClosedError<T>(pub T);
+
+impl<T> ClosedError<T> {
+    /// Returns the object that could not be tracked.
+    pub fn into_inner(self) -> T {
+        self.0
     }
 }
 
-impl<T> Inventory<T> {
-    /// Creates a new inventory.
-    pub fn new() -> Inventory<T> {
-        Inventory::default()
+impl<T> fmt::Debug for ClosedError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.write_str("ClosedError(..)")
     }
+}
 
-    fn lock_items(&self) -> MutexGuard<Items<T>> {
-        let mut guard = self.inner.items.lock().unwrap();
-        guard.gc_if_needed();
-        guard
+impl<T> fmt::Display for ClosedError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.write_str("cannot track an object in a closed inventory")
     }
+}
 
-    /// Returns the number of tracked object.
-    pub fn len(&self) -> usize {
-        self.lock_items().len()
+#[cfg(feature = "std")]
+impl<T> std::error::Error for ClosedError<T> {}
+
+/// Why an object could not be tracked.
+enum Rejection<T> {
+    Closed(T),
+    OverQuota(T),
+}
+
+/// A birth or a death in an inventory, see `Inventory::set_observer`.
+#[derive(Debug)]
+pub enum CensusEvent<T> {
+    /// An object was tracked.
+    Birth {
+        /// The id of the new object.
+        id: u64,
+        /// A handle to the new object.
+        object: TrackedObject<T>,
+    },
+    /// An object was untracked.
+    Death {
+        /// The id of the dead object.
+        id: u64,
+    },
+}
+
+/// The order in which `Inventory::list` returns the tracked objects.
+///
+/// The default is `Unspecified`, or `InsertionAsc` if the
+/// `deterministic-order` feature is enabled.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub enum ListOrder {
+    /// No particular order. This is the cheapest option.
+    #[cfg_attr(not(feature = "deterministic-order"), default)]
+    Unspecified,
+    /// From the oldest to the most recently tracked object.
+    #[cfg_attr(feature = "deterministic-order", default)]
+    InsertionAsc,
+    /// From the most recently tracked to the oldest object.
+    InsertionDesc,
+}
+
+/// How the internal vector of the inventory grows when it is full.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum GrowthPolicy {
+    /// The vector doubles its capacity, as a `Vec` does.
+    #[default]
+    Doubling,
+    /// The vector grows by the given number of slots.
+    Fixed(usize),
+    /// The vector capacity is multiplied by the given factor.
+    Exponential(f64),
+}
+
+impl GrowthPolicy {
+    /// Returns the number of slots to reserve when the vector
+    /// is full, or `None` to let the vector decide.
+    fn additional(self, capacity: usize) -> Option<usize> {
+        match self {
+            GrowthPolicy::Doubling => None,
+            GrowthPolicy::Fixed(increment) => Some(increment.max(1)),
+            GrowthPolicy::Exponential(factor) => {
+                // `f64::ceil` is not available without `std`.
+                let additional = capacity as f64 * (factor - 1.0);
+                let mut rounded = additional as usize;
+                if (rounded as f64) < additional {
+                    rounded += 1;
+                }
+                Some(rounded.max(1))
+            }
+        }
     }
+}
 
-    /// Takes a snapshot of the list of tracked object.
+/// The expected workload of an inventory, see `Inventory::optimize_for`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
+pub enum AccessHint {
+    /// The inventory is mostly listed and searched.
     ///
-    /// Note that the list is a simple `Vec` of tracked object.
-    /// As a result, it is a consistent snapshot of the
-    /// list of living instance at the time of the call,
+    /// Dead references are collected eagerly, to keep scans short,
+    /// and the internal vector grows by smaller steps.
+    ReadHeavy,
+    /// Objects are mostly tracked and dropped.
     ///
-    /// Obviously, instances may have been created after the call.
-    /// They will obviously not appear in the snapshot.
+    /// Dead references are collected lazily, and births and deaths
+    /// only wake up the threads that are actually waiting, using
+    /// the `Park` wait strategy.
+    WriteHeavy,
+    /// The default configuration.
+    #[default]
+    Balanced,
+}
+
+/// A builder to configure and create an `Inventory`.
+///
+/// ```rust
+/// use census::Inventory;
+///
+/// let inventory: Inventory<u32> = Inventory::builder()
+///     .name("connections")
+///     .max_alive(100)
+///     .build();
+/// assert_eq!(inventory.name(), Some("connections"));
+/// ```
+pub struct InventoryBuilder<T> {
+    name: Option<String>,
+    max_alive: Option<usize>,
+    quota: Option<Box<dyn Quota<T> + Send + Sync>>,
+    #[cfg(feature = "std")]
+    wait_strategy: WaitStrategy,
+    list_order: ListOrder,
+    growth_policy: GrowthPolicy,
+    capacity: usize,
+    strict: bool,
+    slab: bool,
+    parent: Option<Inventory<T>>,
+}
+
+impl<T> Default for InventoryBuilder<T> {
+    fn default() -> Self {
+        InventoryBuilder {
+            name: None,
+            max_alive: None,
+            quota: None,
+            #[cfg(feature = "std")]
+            wait_strategy: WaitStrategy::default(),
+            list_order: ListOrder::default(),
+            growth_policy: GrowthPolicy::default(),
+            capacity: 0,
+            strict: false,
+            slab: false,
+            parent: None,
+        }
+    }
+}
+
+impl<T> InventoryBuilder<T> {
+    /// Creates a builder with the default configuration.
+    pub fn new() -> InventoryBuilder<T> {
+        InventoryBuilder::default()
+    }
+
+    /// Sets the name of the inventory.
     ///
-    /// ```rust
-    /// use census::{Inventory, TrackedObject};
+    /// The name is used to identify the inventory in reports.
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the maximum number of objects alive at the same time.
     ///
-    /// let inventory = Inventory::new();
+    /// Objects exceeding this limit are rejected by `Inventory::try_track`.
+    pub fn max_alive(mut self, max: usize) -> Self {
+        self.max_alive = Some(max);
+        self
+    }
+
+    /// Limits the number of objects sharing the same key that can be
+    /// alive at the same time.
     ///
-    /// let one = inventory.track("one".to_string());
-    /// let living_instances: Vec<TrackedObject<String>> = inventory.list();
-    /// let two = inventory.track("two".to_string());
+    /// The key of an object is computed by `key_fn`.
+    /// Objects exceeding the quota are rejected by `Inventory::try_track`.
+    #[cfg(feature = "std")]
+    pub fn key_quota<K, F>(mut self, limit_per_key: usize, key_fn: F) -> Self
+    where
+        K: Eq + Hash + Send + Sync + 'static,
+        F: Fn(&T) -> K + Send + Sync + 'static,
+    {
+        self.quota = Some(Box::new(KeyQuota {
+            limit_per_key,
+            key_fn,
+            counts: HashMap::new(),
+        }));
+        self
+    }
+
+    /// Sets the strategy used by threads waiting on the inventory.
     ///
-    /// // our snapshot is a bit old.
-    /// assert_eq!(living_instances.len(), 1);
+    /// ```rust
+    /// use census::{Inventory, WaitStrategy};
     ///
-    /// // a fresher snapshot would contain our new element.
-    /// assert_eq!(inventory.list().len(), 2);
+    /// let inventory: Inventory<u32> = Inventory::builder()
+    ///     .wait_strategy(WaitStrategy::Park)
+    ///     .build();
+    /// assert!(inventory.is_empty());
     /// ```
+    #[cfg(feature = "std")]
+    pub fn wait_strategy(mut self, wait_strategy: WaitStrategy) -> Self {
+        self.wait_strategy = wait_strategy;
+        self
+    }
+
+    /// Sets the order in which `Inventory::list` returns the tracked objects.
     ///
-    /// Also, the instance in the snapshot itself
-    /// are considered "living".
-    ///
-    /// As a result, as long as a snapshot is not dropped,
-    /// all of its instances will be part of the inventory.
+    /// Any order other than `ListOrder::Unspecified` makes
+    /// the removal of dead objects slightly more expensive.
     ///
     /// ```rust
-    /// # use census::{Inventory, TrackedObject};
-    ///
-    /// let inventory = Inventory::new();
-    ///
-    /// let one = inventory.track("one".to_string());
-    /// let living_instances: Vec<TrackedObject<String>> = inventory.list();
+    /// use census::{Inventory, ListOrder};
     ///
-    /// // let's drop one here
-    /// drop(one);
-    ///
-    /// // The instance is technically still in the inventory
-    /// // as our previous snapshot is extending its life...
-    /// assert_eq!(inventory.list().len(), 1);
+    /// let inventory = Inventory::builder()
+    ///     .list_order(ListOrder::InsertionDesc)
+    ///     .build();
+    /// let objs: Vec<_> = (0..3).map(|i| inventory.track(i)).collect();
+    /// let values: Vec<i32> = inventory.list().iter().map(|obj| **obj).collect();
+    /// assert_eq!(values, vec![2, 1, 0]);
+    /// ```
+    pub fn list_order(mut self, list_order: ListOrder) -> Self {
+        self.list_order = list_order;
+        self
+    }
+
+    /// Sets how the internal vector of the inventory grows when it is full.
     ///
-    /// // If we drop our previous snapshot however...
-    /// drop(living_instances);
+    /// ```rust
+    /// use census::{GrowthPolicy, Inventory};
     ///
-    /// // `one` is really untracked.
-    /// assert!(inventory.list().is_empty());
+    /// let inventory = Inventory::builder()
+    ///     .growth_policy(GrowthPolicy::Fixed(64))
+    ///     .build();
+    /// let _one = inventory.track(1);
+    /// assert_eq!(inventory.stats().capacity, 64);
     /// ```
-    ///
-    pub fn list(&self) -> Vec<TrackedObject<T>> {
-        self.lock_items().list_arc()
+    pub fn growth_policy(mut self, growth_policy: GrowthPolicy) -> Self {
+        self.growth_policy = growth_policy;
+        self
     }
 
-    /// This function blocks until there are no more items in the inventory.
+    /// Preallocates room for `capacity` objects in the internal vector of
+    /// the inventory.
     ///
-    /// It is a helper calling
-    /// ```ignore
-    /// self.wait_until_predicate(|count| count == 0)
-    /// ```
+    /// The capacity is only a hint: the vector still grows past it when
+    /// needed, following the growth policy of the inventory.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Enables the strict mode, which checks the accounting of the living
+    /// objects after every birth and death.
     ///
-    /// Note it is very easy to misuse this function and create a deadlock.
-    /// For instance, if any living TrackedObject is on the stack at the moment of the call,
-    /// it will not get dropped, and the inventory cannot become empty.
-    pub fn wait_until_empty(&self) {
-        self.wait_until_predicate(|count| count == 0)
+    /// The strict mode is meant to catch bugs during development: it makes
+    /// births and deaths linear in the number of objects, and
+    /// it is a no-op in release builds.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
     }
 
-    /// This function blocks until the number of items in the repository matches a specific
-    /// predicate.
+    /// Stores the tracked objects in a slab owned by the inventory,
+    /// instead of allocating each of them separately.
     ///
-    /// See also `wait_until_empty`.
+    /// The slab is allocated by pages, which never move as the slab grows:
+    /// tracked objects still deref without locking the inventory.
+    /// A few pages hold millions of objects, and the slots of dead objects
+    /// are reused. In exchange, the pages are only released once the
+    /// inventory and all of its objects are dropped.
     ///
-    /// Note it is very easy to misuse this function and create a deadlock.
-    /// For instance, if any living TrackedObject is on the stack at the moment of the call,
-    /// it will not get dropped, and the inventory cannot become empty.
-    pub fn wait_until_predicate<F: Fn(usize) -> bool>(&self, predicate_on_count: F) {
-        let mut count = self.lock_items();
-        while !predicate_on_count(count.alive_count()) {
-            count = self.inner.condvar.wait(count).unwrap();
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::builder().slab(true).build();
+    /// let objs: Vec<_> = (0..1_000).map(|i| inventory.track(i)).collect();
+    /// assert_eq!(inventory.len(), 1_000);
+    /// drop(objs);
+    /// assert!(inventory.is_empty());
+    /// ```
+    pub fn slab(mut self, slab: bool) -> Self {
+        self.slab = slab;
+        self
+    }
+
+    /// Creates the inventory.
+    pub fn build(self) -> Inventory<T> {
+        let items = Items {
+            max_alive: self.max_alive,
+            quota: self.quota,
+            list_order: self.list_order,
+            strict: self.strict,
+            #[cfg(feature = "std")]
+            wait_strategy: self.wait_strategy,
+            growth_policy: self.growth_policy,
+            items: Vec::with_capacity(self.capacity),
+            ..Items::default()
+        };
+        Inventory {
+            inner: Arc::new(InnerInventory {
+                name: self.name,
+                items: RwLock::new(items),
+                #[cfg(feature = "std")]
+                waiters: Mutex::new(()),
+                #[cfg(feature = "std")]
+                condvar: Condvar::new(),
+                alive: AtomicUsize::new(0),
+                closed: sync::atomic::AtomicBool::new(false),
+                next_id: AtomicU64::new(0),
+                death_seq: AtomicU64::new(0),
+                parent: self.parent,
+                slab: if self.slab {
+                    Some(Arc::new(Slab::new()))
+                } else {
+                    None
+                },
+            }),
         }
     }
+}
 
-    /// Starts tracking a given `T` object.
-    pub fn track(&self, item: T) -> TrackedObject<T> {
-        let item_arc = Arc::new(InnerTrackedObject {
-            census: self.clone(),
-            item,
-        });
-        let item_weak = Arc::downgrade(&item_arc);
-        let mut items_lock = self.lock_items();
-        items_lock.items.push(item_weak);
-        items_lock.record_birth();
-        self.inner.condvar.notify_all();
-        TrackedObject { inner: item_arc }
+impl<T> Default for Inventory<T> {
+    fn default() -> Self {
+        InventoryBuilder::new().build()
     }
 }
 
-/// Your tracked object.
-///
-/// A tracked object contains reference counting logic and an
-/// `Arc` to your object. It is cloneable but calling clone will
-/// not clone your internal object.
-///
-/// Your object cannot be mutated. You can borrow it using
-/// the `Deref` interface.
-#[derive(Clone)]
-pub struct TrackedObject<T> {
-    inner: Arc<InnerTrackedObject<T>>,
+impl<T> Clone for Inventory<T> {
+    fn clone(&self) -> Self {
+        Inventory {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// A handle to an inventory that does not keep it alive.
+///
+/// It is obtained with `Inventory::downgrade`. The inventory is kept alive
+/// by its `Inventory` handles and by its living tracked objects.
+pub struct WeakInventory<T> {
+    inner: Weak<InnerInventory<T>>,
+}
+
+impl<T> WeakInventory<T> {
+    /// Returns a handle to the inventory, or `None` if it was dropped.
+    pub fn upgrade(&self) -> Option<Inventory<T>> {
+        self.inner.upgrade().map(|inner| Inventory { inner })
+    }
+}
+
+impl<T> Clone for WeakInventory<T> {
+    fn clone(&self) -> Self {
+        WeakInventory {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// Summarizes the inventory, without listing its objects.
+///
+/// `slots` is the length of the internal vector, which includes the
+/// references to dead objects that were not collected yet.
+///
+/// ```rust
+/// use census::Inventory;
+///
+/// let inventory = Inventory::new();
+/// let _one = inventory.track(1);
+/// assert_eq!(format!("{:?}", inventory), "Inventory { alive: 1, slots: 1 }");
+/// ```
+impl<T> fmt::Debug for Inventory<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let (alive, slots) = {
+            let items = self.read_items();
+            (items.alive_count(), items.items.len())
+        };
+        let mut debug = f.debug_struct("Inventory");
+        if let Some(name) = self.name() {
+            debug.field("name", &name);
+        }
+        debug.field("alive", &alive).field("slots", &slots).finish()
+    }
+}
+
+/// Displays the name of the inventory, if any, and its number of living objects.
+///
+/// ```rust
+/// use census::Inventory;
+///
+/// let inventory = Inventory::with_name("files");
+/// let _one = inventory.track(1);
+/// assert_eq!(inventory.to_string(), "Inventory(files, alive=1)");
+/// ```
+impl<T> fmt::Display for Inventory<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let alive = self.len();
+        match self.name() {
+            Some(name) => write!(f, "Inventory({}, alive={})", name, alive),
+            None => write!(f, "Inventory(alive={})", alive),
+        }
+    }
 }
 
-struct InnerTrackedObject<T> {
-    census: Inventory<T>,
-    item: T,
-}
+impl<T> Inventory<T> {
+    /// Creates a new inventory.
+    pub fn new() -> Inventory<T> {
+        Inventory::default()
+    }
+
+    /// Creates a new inventory with a name.
+    ///
+    /// The name is used to identify the inventory in reports.
+    pub fn with_name<S: Into<String>>(name: S) -> Inventory<T> {
+        InventoryBuilder::new().name(name).build()
+    }
+
+    /// Creates a child inventory.
+    ///
+    /// The objects tracked in the child are also tracked in this inventory:
+    /// they are counted by `len` and listed by `list` on both inventories.
+    /// Their births and deaths wake up the threads waiting on both inventories.
+    /// The maximum, capacity alarm, quota, and closing of this inventory only
+    /// apply to the objects tracked directly in it: objects tracked in a child
+    /// do not count against the maximum of its parent.
+    ///
+    /// A child inventory keeps its parent alive, but not the other way around.
+    /// Object ids are unique across all of the inventories of a hierarchy.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let global = Inventory::new();
+    /// let subsystem = global.new_child();
+    /// let _one = subsystem.track(1);
+    /// let _two = global.track(2);
+    /// assert_eq!(subsystem.len(), 1);
+    /// assert_eq!(global.len(), 2);
+    /// ```
+    pub fn new_child(&self) -> Inventory<T> {
+        let builder = {
+            let items = self.read_items();
+            InventoryBuilder {
+                #[cfg(feature = "std")]
+                wait_strategy: items.wait_strategy,
+                growth_policy: items.growth_policy,
+                slab: self.inner.slab.is_some(),
+                parent: Some(self.clone()),
+                ..InventoryBuilder::default()
+            }
+        };
+        builder.build()
+    }
+
+    /// Tunes the inventory for the given workload.
+    ///
+    /// This reconfigures at once the garbage collection of dead references,
+    /// the growth policy of the internal vector, and the wait strategy.
+    ///
+    /// ```rust
+    /// use census::{AccessHint, Inventory};
+    ///
+    /// let inventory: Inventory<u32> = Inventory::new();
+    /// inventory.optimize_for(AccessHint::WriteHeavy);
+    /// ```
+    pub fn optimize_for(&self, hint: AccessHint) {
+        let (gc_dead_ratio, growth_policy) = match hint {
+            AccessHint::ReadHeavy => (0.25, GrowthPolicy::Exponential(1.5)),
+            AccessHint::WriteHeavy => (0.75, GrowthPolicy::Doubling),
+            AccessHint::Balanced => (DEFAULT_GC_DEAD_RATIO, GrowthPolicy::Doubling),
+        };
+        let mut items = self.lock_items();
+        items.gc_dead_ratio = gc_dead_ratio;
+        items.growth_policy = growth_policy;
+        #[cfg(feature = "std")]
+        {
+            items.wait_strategy = match hint {
+                AccessHint::WriteHeavy => WaitStrategy::Park,
+                AccessHint::ReadHeavy | AccessHint::Balanced => WaitStrategy::Condvar,
+            };
+        }
+    }
+
+    /// Sets the ratio of dead references in the internal vector above which
+    /// they are removed, the next time the inventory is locked.
+    ///
+    /// The default ratio is `0.5`. Lower ratios reclaim memory more eagerly,
+    /// at the cost of more frequent sweeps. Use `gc` to remove the dead
+    /// references right away.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ratio` is not between `0.0` and `1.0`.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory: Inventory<u32> = Inventory::new();
+    /// inventory.set_gc_ratio(0.1);
+    /// ```
+    pub fn set_gc_ratio(&self, ratio: f64) {
+        assert!(
+            (0.0..=1.0).contains(&ratio),
+            "The garbage collection ratio must be between 0 and 1."
+        );
+        self.lock_items().gc_dead_ratio = ratio;
+    }
+
+    /// Returns the name of the inventory, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.inner.name.as_deref()
+    }
+
+    /// Creates a new inventory in which at most `max` objects
+    /// can be alive at the same time.
+    ///
+    /// Objects exceeding this limit are rejected by `try_track`.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::with_max(1);
+    /// let one = inventory.try_track(1).unwrap();
+    /// assert_eq!(inventory.try_track(2).unwrap_err(), 2);
+    /// drop(one);
+    /// assert!(inventory.try_track(2).is_ok());
+    /// ```
+    pub fn with_max(max: usize) -> Inventory<T> {
+        InventoryBuilder::new().max_alive(max).build()
+    }
+
+    /// Creates a new inventory with room for `capacity` objects,
+    /// so that tracking them does not reallocate the internal vector.
+    ///
+    /// The capacity is only a hint: the vector still grows past it when
+    /// needed, following the growth policy of the inventory, and
+    /// `shrink_to_fit` releases the unused room.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::with_capacity(100);
+    /// assert!(inventory.stats().capacity >= 100);
+    /// let _one = inventory.track(1);
+    /// ```
+    pub fn with_capacity(capacity: usize) -> Inventory<T> {
+        InventoryBuilder::new().capacity(capacity).build()
+    }
+
+    /// Sets a callback invoked whenever the utilization of the inventory,
+    /// `alive / max`, crosses `ratio` upward.
+    ///
+    /// The callback is called with the number of living objects and the
+    /// maximum number of living objects. It fires once per crossing,
+    /// from the thread tracking the object, without holding the inventory lock.
+    ///
+    /// The alarm never fires for inventories without a maximum.
+    /// See `with_max`.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let inventory = Inventory::with_max(10);
+    /// let alarms = Arc::new(AtomicUsize::new(0));
+    /// let alarms_clone = alarms.clone();
+    /// inventory.set_capacity_alarm(0.5, move |_alive, _max| {
+    ///     alarms_clone.fetch_add(1, Ordering::SeqCst);
+    /// });
+    /// let objs: Vec<_> = (0..6).map(|i| inventory.track(i)).collect();
+    /// assert_eq!(alarms.load(Ordering::SeqCst), 1);
+    /// ```
+    pub fn set_capacity_alarm<F>(&self, ratio: f64, f: F)
+    where
+        F: Fn(usize, usize) + Send + Sync + 'static,
+    {
+        self.lock_items().capacity_alarm = Some(CapacityAlarm {
+            ratio,
+            callback: Arc::new(f),
+        });
+    }
+
+    /// Creates a new inventory in which at most `limit_per_key` objects
+    /// sharing the same key can be alive at the same time.
+    ///
+    /// The key of an object is computed by `key_fn`.
+    /// Objects exceeding the quota are rejected by `try_track`.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::with_key_quota(1, |s: &(&str, u32)| s.0);
+    /// let _a = inventory.try_track(("tenant-a", 1)).unwrap();
+    /// let _b = inventory.try_track(("tenant-b", 2)).unwrap();
+    /// assert_eq!(inventory.try_track(("tenant-a", 3)).unwrap_err(), ("tenant-a", 3));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn with_key_quota<K, F>(limit_per_key: usize, key_fn: F) -> Inventory<T>
+    where
+        K: Eq + Hash + Send + Sync + 'static,
+        F: Fn(&T) -> K + Send + Sync + 'static,
+    {
+        InventoryBuilder::new()
+            .key_quota(limit_per_key, key_fn)
+            .build()
+    }
+
+    /// Returns a builder to configure a new inventory.
+    pub fn builder() -> InventoryBuilder<T> {
+        InventoryBuilder::new()
+    }
+
+    /// Returns a handle to the inventory that does not keep it alive.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::<u32>::new();
+    /// let weak = inventory.downgrade();
+    /// assert!(weak.upgrade().is_some());
+    /// drop(inventory);
+    /// assert!(weak.upgrade().is_none());
+    /// ```
+    pub fn downgrade(&self) -> WeakInventory<T> {
+        WeakInventory {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// Returns true if `obj` was tracked by this inventory,
+    /// or by one of its child inventories.
+    ///
+    /// This compares the identity of the inventories, and does not
+    /// look at the tracked objects.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let other = Inventory::new();
+    /// let one = inventory.track(1);
+    /// assert!(inventory.contains(&one));
+    /// assert!(!other.contains(&one));
+    /// ```
+    pub fn contains(&self, obj: &TrackedObject<T>) -> bool {
+        let mut inventory = Some(&obj.inner.census);
+        while let Some(current) = inventory {
+            if Arc::ptr_eq(&current.inner, &self.inner) {
+                return true;
+            }
+            inventory = current.inner.parent.as_ref();
+        }
+        false
+    }
+
+    /// Acquires the lock for writing.
+    ///
+    /// The state of the inventory is kept consistent even if a thread panics
+    /// while holding the lock, so that poisoning is ignored. In particular,
+    /// dropping a tracked object never panics because of a poisoned lock.
+    fn lock_items(&self) -> RwLockWriteGuard<'_, Items<T>> {
+        let mut guard = sync::write(&self.inner.items);
+        guard.gc_if_needed();
+        guard
+    }
+
+    /// Acquires the lock for reading.
+    ///
+    /// Garbage collection requires the write lock. When it is due, the read
+    /// lock is released, the write lock is taken to collect, and the read
+    /// lock is taken again.
+    fn read_items(&self) -> RwLockReadGuard<'_, Items<T>> {
+        let guard = sync::read(&self.inner.items);
+        if !guard.should_gc() {
+            return guard;
+        }
+        drop(guard);
+        drop(self.lock_items());
+        sync::read(&self.inner.items)
+    }
+
+    /// Releases the lock and blocks until the inventory changes, or until
+    /// `timeout` elapses. Spurious wakeups are possible.
+    #[cfg(feature = "std")]
+    fn wait_for_change<'a>(
+        &'a self,
+        mut items: RwLockWriteGuard<'a, Items<T>>,
+        timeout: Option<Duration>,
+    ) -> RwLockWriteGuard<'a, Items<T>> {
+        match items.wait_strategy {
+            WaitStrategy::Condvar => {
+                items.condvar_waiters += 1;
+                // Notifiers hold the write lock while notifying, so that
+                // taking `waiters` before releasing it loses no wakeup.
+                let waiters = sync::lock(&self.inner.waiters);
+                drop(items);
+                let waiters = match timeout {
+                    Some(timeout) => {
+                        self.inner
+                            .condvar
+                            .wait_timeout(waiters, timeout)
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .0
+                    }
+                    None => self
+                        .inner
+                        .condvar
+                        .wait(waiters)
+                        .unwrap_or_else(PoisonError::into_inner),
+                };
+                drop(waiters);
+                let mut items = self.lock_items();
+                items.condvar_waiters -= 1;
+                items
+            }
+            WaitStrategy::Park => {
+                // An unpark happening between the release of the lock and
+                // the call to park makes park return immediately.
+                items.parked_threads.push(thread::current());
+                drop(items);
+                match timeout {
+                    Some(timeout) => thread::park_timeout(timeout),
+                    None => thread::park(),
+                }
+                let mut items = self.lock_items();
+                let current_id = thread::current().id();
+                items.parked_threads.retain(|t| t.id() != current_id);
+                items
+            }
+        }
+    }
+
+    /// Publishes the number of living objects after births or deaths.
+    fn publish_count(&self, items: &mut Items<T>) {
+        if cfg!(debug_assertions) && items.strict {
+            items.check_consistency();
+        }
+        self.inner
+            .alive
+            .store(items.alive_count(), Ordering::Relaxed);
+        #[cfg(feature = "tokio")]
+        {
+            if let Some(count_sender) = items.count_sender.as_ref() {
+                count_sender.send_replace(items.alive_count());
+            }
+        }
+        #[cfg(feature = "std")]
+        self.notify_waiters(items);
+    }
+
+    /// Wakes up the threads waiting for the inventory to change.
+    ///
+    /// Both kinds of waiters are woken up, as the wait strategy may have
+    /// changed while they were waiting. Births and deaths are much more
+    /// frequent than waits, so that the condition variable is only
+    /// notified if a thread is waiting on it.
+    #[cfg(feature = "std")]
+    fn notify_waiters(&self, items: &mut Items<T>) {
+        if items.condvar_waiters > 0 {
+            let _waiters = sync::lock(&self.inner.waiters);
+            self.inner.condvar.notify_all();
+        }
+        for parked_thread in items.parked_threads.drain(..) {
+            parked_thread.unpark();
+        }
+    }
+
+    /// Calls `f` on each living object, while holding the inventory lock,
+    /// until `f` returns `ControlFlow::Break`.
+    ///
+    /// Objects are upgraded one at a time. If a temporary handle turns out to
+    /// be the last handle of its object, dropping it would untrack the object,
+    /// and lock the inventory again. Such objects are only dropped after
+    /// the lock is released.
+    fn visit<B, F>(&self, mut f: F) -> Option<B>
+    where
+        F: FnMut(&TrackedObject<T>) -> ControlFlow<B>,
+    {
+        let mut orphans = Vec::new();
+        let res = {
+            let items = self.read_items();
+            let mut res = None;
+            for obj in items.living_objects() {
+                let flow = f(&obj);
+                if let Some(orphan) = Handle::into_inner(obj.inner) {
+                    orphans.push(orphan);
+                }
+                if let ControlFlow::Break(b) = flow {
+                    res = Some(b);
+                    break;
+                }
+            }
+            res
+        };
+        drop(orphans);
+        res
+    }
+
+    /// Returns the number of tracked objects.
+    ///
+    /// This is much cheaper than `list().len()`: no snapshot is built,
+    /// so the lives of the objects are not extended.
+    ///
+    /// The count is the number of objects whose death was not recorded yet.
+    /// Objects kept alive by a snapshot held elsewhere are still counted,
+    /// even if all of their original handles were dropped.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let one = inventory.track(1);
+    /// let snapshot = inventory.list();
+    /// drop(one);
+    /// assert_eq!(inventory.len(), 1);
+    /// drop(snapshot);
+    /// assert_eq!(inventory.len(), 0);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.read_items().len()
+    }
+
+    /// Returns true if there are no tracked objects.
+    ///
+    /// See `len`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes the references to dead objects from the internal vector,
+    /// regardless of the usual garbage collection heuristic.
+    ///
+    /// Returns the length of the internal vector before and after the sweep.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = (0..10).map(|i| inventory.track(i)).collect();
+    /// let _kept = objs[0].clone();
+    /// drop(objs);
+    /// let (_before, after) = inventory.compact();
+    /// assert_eq!(after, 1);
+    /// ```
+    pub fn compact(&self) -> (usize, usize) {
+        // `lock_items` would collect the dead references before measuring.
+        let mut items = sync::write(&self.inner.items);
+        let len_before = items.items.len();
+        items.gc();
+        (len_before, items.items.len())
+    }
+
+    /// Removes the references to dead objects from the internal vector
+    /// right away, regardless of the garbage collection ratio.
+    ///
+    /// See `compact` to also measure the sweep.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = (0..10).map(|i| inventory.track(i)).collect();
+    /// let _kept = objs[..6].to_vec();
+    /// drop(objs);
+    /// inventory.gc();
+    /// assert_eq!(inventory.stats().gc_passes, 1);
+    /// ```
+    pub fn gc(&self) {
+        self.compact();
+    }
+
+    /// Removes the references to dead objects from the internal vector,
+    /// and releases its unused capacity.
+    ///
+    /// The vector never shrinks on its own: this returns the memory
+    /// reserved during a burst of objects, once the burst is over.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::with_capacity(1_000);
+    /// let _one = inventory.track(1);
+    /// inventory.shrink_to_fit();
+    /// assert!(inventory.stats().capacity < 1_000);
+    /// ```
+    pub fn shrink_to_fit(&self) {
+        let mut items = self.lock_items();
+        items.gc();
+        items.items.shrink_to_fit();
+    }
+
+    /// Returns a snapshot of the inventory counters.
+    ///
+    /// All of the counters are read under a single lock acquisition,
+    /// so that they are consistent with one another.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let one = inventory.track(1);
+    /// drop(one);
+    /// let _two = inventory.track(2);
+    ///
+    /// let stats = inventory.stats();
+    /// assert_eq!(stats.alive, 1);
+    /// assert_eq!(stats.peak, 1);
+    /// assert_eq!(stats.total_tracked, 2);
+    /// ```
+    pub fn stats(&self) -> CensusStats {
+        self.read_items().stats()
+    }
+
+    /// Returns the sequence number of the last death recorded, if any.
+    ///
+    /// Deaths are numbered from 1, in the order they are recorded.
+    /// Together with the object ids, which are assigned in the order of the
+    /// births, this orders all of the births and deaths of the inventory.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let one = inventory.track(1);
+    /// assert_eq!(inventory.last_death_seq(), None);
+    /// drop(one);
+    /// assert_eq!(inventory.last_death_seq(), Some(1));
+    /// ```
+    pub fn last_death_seq(&self) -> Option<u64> {
+        match self.inner.death_seq.load(Ordering::Relaxed) {
+            0 => None,
+            seq => Some(seq),
+        }
+    }
+
+    /// Returns an estimate of the memory used by the census machinery, in bytes.
+    ///
+    /// The estimate covers the internal vector of weak references, and the
+    /// allocations of the living objects, including their reference counts.
+    /// It excludes any heap memory owned by the `T` values themselves.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let empty = inventory.estimated_memory();
+    /// let _one = inventory.track(1u64);
+    /// assert!(inventory.estimated_memory() > empty);
+    /// ```
+    pub fn estimated_memory(&self) -> usize {
+        let items = self.read_items();
+        let weak_refs =
+            items.items.capacity() * mem::size_of::<WeakHandle<InnerTrackedObject<T>>>();
+        match self.inner.slab {
+            // The pages of the slab are kept for reuse, even if empty.
+            Some(ref slab) => weak_refs + slab.allocated_bytes(),
+            None => {
+                // Each object lives in an `Arc` allocation, holding two reference counts.
+                let object_size =
+                    mem::size_of::<InnerTrackedObject<T>>() + 2 * mem::size_of::<usize>();
+                weak_refs + items.alive_count() * object_size
+            }
+        }
+    }
+
+    /// Returns the highest number of objects alive at the same time
+    /// since the creation of the inventory.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = (0..3).map(|i| inventory.track(i)).collect();
+    /// drop(objs);
+    /// let _one = inventory.track(1);
+    /// assert_eq!(inventory.peak_alive(), 3);
+    /// ```
+    pub fn peak_alive(&self) -> usize {
+        self.read_items().peak
+    }
+
+    /// Returns the number of objects tracked since the creation of the inventory,
+    /// including the objects created with `map`.
+    ///
+    /// Contrary to `len`, this counter never decreases.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let one = inventory.track(1);
+    /// let _two = one.map(|i| i + 1);
+    /// drop(one);
+    /// assert_eq!(inventory.total_tracked(), 2);
+    /// ```
+    pub fn total_tracked(&self) -> u64 {
+        self.read_items().total_tracked
+    }
+
+    /// Returns the number of objects untracked since the creation of the inventory.
+    ///
+    /// The number of living objects is always the number of objects
+    /// tracked minus the number of objects dropped.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let one = inventory.track(1);
+    /// let _two = inventory.track(2);
+    /// drop(one);
+    /// assert_eq!(inventory.total_dropped(), 1);
+    /// ```
+    pub fn total_dropped(&self) -> u64 {
+        self.read_items().total_dropped
+    }
+
+    /// Takes a snapshot of the list of tracked object.
+    ///
+    /// Note that the list is a simple `Vec` of tracked object.
+    /// As a result, it is a consistent snapshot of the
+    /// list of living instance at the time of the call,
+    ///
+    /// Obviously, instances may have been created after the call.
+    /// They will obviously not appear in the snapshot.
+    ///
+    /// The order of the objects is set by `InventoryBuilder::list_order`.
+    /// By default, there is no guarantee on their order, unless the
+    /// `deterministic-order` feature is enabled.
+    ///
+    /// ```rust
+    /// use census::{Inventory, TrackedObject};
+    ///
+    /// let inventory = Inventory::new();
+    ///
+    /// let one = inventory.track("one".to_string());
+    /// let living_instances: Vec<TrackedObject<String>> = inventory.list();
+    /// let two = inventory.track("two".to_string());
+    ///
+    /// // our snapshot is a bit old.
+    /// assert_eq!(living_instances.len(), 1);
+    ///
+    /// // a fresher snapshot would contain our new element.
+    /// assert_eq!(inventory.list().len(), 2);
+    /// ```
+    ///
+    /// Also, the instance in the snapshot itself
+    /// are considered "living".
+    ///
+    /// As a result, as long as a snapshot is not dropped,
+    /// all of its instances will be part of the inventory.
+    ///
+    /// ```rust
+    /// # use census::{Inventory, TrackedObject};
+    ///
+    /// let inventory = Inventory::new();
+    ///
+    /// let one = inventory.track("one".to_string());
+    /// let living_instances: Vec<TrackedObject<String>> = inventory.list();
+    ///
+    /// // let's drop one here
+    /// drop(one);
+    ///
+    /// // The instance is technically still in the inventory
+    /// // as our previous snapshot is extending its life...
+    /// assert_eq!(inventory.list().len(), 1);
+    ///
+    /// // If we drop our previous snapshot however...
+    /// drop(living_instances);
+    ///
+    /// // `one` is really untracked.
+    /// assert!(inventory.list().is_empty());
+    /// ```
+    ///
+    pub fn list(&self) -> Vec<TrackedObject<T>> {
+        self.read_items().list_arc()
+    }
+
+    /// Takes a snapshot of the tracked objects into `buf`, reusing its capacity.
+    ///
+    /// `buf` is cleared first, before locking the inventory: the objects it
+    /// held are released, and are only listed again if they are still alive.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let one = inventory.track(1);
+    /// let mut buf = Vec::new();
+    /// inventory.list_into(&mut buf);
+    /// assert_eq!(buf.len(), 1);
+    /// drop(one);
+    /// inventory.list_into(&mut buf);
+    /// assert!(buf.is_empty());
+    /// ```
+    pub fn list_into(&self, buf: &mut Vec<TrackedObject<T>>) {
+        buf.clear();
+        let items = self.read_items();
+        buf.extend(items.living_objects());
+        if items.list_order == ListOrder::InsertionDesc {
+            buf.reverse();
+        }
+    }
+
+    /// Takes a snapshot of the living objects, and stops listing them.
+    ///
+    /// The inventory forgets the returned objects: they are no longer
+    /// listed by `list` and its variants, even though they remain alive for
+    /// as long as their handles do. They are still counted by `len` until
+    /// they are dropped, so that `wait_until_empty` waits for them.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let _one = inventory.track(1);
+    /// let drained = inventory.drain();
+    /// assert_eq!(drained.len(), 1);
+    /// assert!(inventory.list().is_empty());
+    /// assert_eq!(inventory.len(), 1);
+    /// ```
+    pub fn drain(&self) -> Vec<TrackedObject<T>> {
+        let mut items = self.lock_items();
+        let drained = items.list_arc();
+        items.items.clear();
+        // Invalidates the cached snapshot.
+        items.version += 1;
+        drained
+    }
+
+    /// Takes a snapshot of the tracked objects, shared between callers.
+    ///
+    /// As long as the inventory did not change, the same snapshot is
+    /// handed to all callers, which amortizes its cost when many readers
+    /// poll the inventory.
+    /// The inventory does not keep the snapshot alive by itself: once all
+    /// callers dropped it, the next call takes a new one.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    /// use std::sync::Arc;
+    ///
+    /// let inventory = Inventory::new();
+    /// let _one = inventory.track(1);
+    /// let first = inventory.list_cached();
+    /// let second = inventory.list_cached();
+    /// assert!(Arc::ptr_eq(&first, &second));
+    /// ```
+    pub fn list_cached(&self) -> Arc<Vec<TrackedObject<T>>> {
+        if let Some(snapshot) = self.read_items().cached_list() {
+            return snapshot;
+        }
+        let mut items = self.lock_items();
+        // Another thread may have rebuilt the cache in the meantime.
+        if let Some(snapshot) = items.cached_list() {
+            return snapshot;
+        }
+        let snapshot = Arc::new(items.list_arc());
+        items.list_cache = Some((items.version, Arc::downgrade(&snapshot)));
+        snapshot
+    }
+
+    /// Takes an immutable snapshot of the tracked objects, that can be
+    /// read and shared between threads without any locking.
+    ///
+    /// The inventory remains usable: objects tracked afterwards are
+    /// simply not part of the frozen view. As for `list`, the frozen view
+    /// extends the life of its objects.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = (0..3).map(|i| inventory.track(i)).collect();
+    /// let frozen = inventory.freeze();
+    /// let _three = inventory.track(3);
+    /// assert_eq!(frozen.len(), 3);
+    /// assert!(frozen.find(|&i| i == 3).is_none());
+    /// ```
+    pub fn freeze(&self) -> FrozenInventory<T> {
+        FrozenInventory {
+            objects: self.list().into(),
+        }
+    }
+
+    /// Takes a snapshot of at most `max` tracked objects.
+    ///
+    /// The inventory stops looking for living objects as soon as `max` of them
+    /// have been found, which makes it cheap to preview a large inventory.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = (0..100).map(|i| inventory.track(i)).collect();
+    /// assert_eq!(inventory.list_capped(3).len(), 3);
+    /// ```
+    pub fn list_capped(&self, max: usize) -> Vec<TrackedObject<T>> {
+        self.read_items().living_objects().take(max).collect()
+    }
+
+    /// Takes a snapshot of the tracked objects matching `predicate`.
+    ///
+    /// This is equivalent to filtering the result of `list`, without
+    /// allocating handles for the objects that do not match.
+    /// `predicate` is called while holding the inventory lock.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = (0..10).map(|i| inventory.track(i)).collect();
+    /// let evens = inventory.list_filtered(|&i| i % 2 == 0);
+    /// assert_eq!(evens.len(), 5);
+    /// ```
+    pub fn list_filtered<P: Fn(&T) -> bool>(&self, predicate: P) -> Vec<TrackedObject<T>> {
+        let mut matches = Vec::new();
+        self.visit(|obj| {
+            if predicate(obj) {
+                matches.push(TrackedObject {
+                    inner: obj.inner.clone(),
+                });
+            }
+            ControlFlow::<()>::Continue(())
+        });
+        matches
+    }
+
+    /// Returns the number of living objects matching `predicate`.
+    ///
+    /// Contrary to `list_filtered`, no handle outlives the call, so that
+    /// counting does not extend the life of the objects.
+    /// `predicate` is called while holding the inventory lock.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = (0..10).map(|i| inventory.track(i)).collect();
+    /// assert_eq!(inventory.count_matching(|&i| i < 4), 4);
+    /// ```
+    pub fn count_matching<P: Fn(&T) -> bool>(&self, predicate: P) -> usize {
+        let mut count = 0;
+        self.visit(|obj| {
+            if predicate(obj) {
+                count += 1;
+            }
+            ControlFlow::<()>::Continue(())
+        });
+        count
+    }
+
+    /// Returns the ids of the living objects.
+    ///
+    /// No handle outlives the call, so that, contrary to `list`, taking
+    /// the snapshot does not extend the life of the objects. Comparing
+    /// two snapshots tells which objects were born and which died in between.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let one = inventory.track(1);
+    /// let _two = inventory.track(2);
+    /// let before = inventory.snapshot_ids();
+    /// let one_id = one.id();
+    /// drop(one);
+    /// let after = inventory.snapshot_ids();
+    /// let died: Vec<u64> = before.into_iter().filter(|id| !after.contains(id)).collect();
+    /// assert_eq!(died, vec![one_id]);
+    /// ```
+    pub fn snapshot_ids(&self) -> Vec<u64> {
+        let mut ids = Vec::new();
+        self.visit(|obj| {
+            ids.push(obj.id());
+            ControlFlow::<()>::Continue(())
+        });
+        ids
+    }
+
+    /// Takes a snapshot of the tracked objects that have been alive
+    /// for at least `age`.
+    ///
+    /// Objects outliving an expected lifetime are often leaked.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    /// use std::time::Duration;
+    ///
+    /// let inventory = Inventory::new();
+    /// let _one = inventory.track(1);
+    /// assert!(inventory.list_older_than(Duration::from_secs(3600)).is_empty());
+    /// assert_eq!(inventory.list_older_than(Duration::ZERO).len(), 1);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn list_older_than(&self, age: Duration) -> Vec<TrackedObject<T>> {
+        let mut old = Vec::new();
+        self.visit(|obj| {
+            if obj.age() >= age {
+                old.push(TrackedObject {
+                    inner: obj.inner.clone(),
+                });
+            }
+            ControlFlow::<()>::Continue(())
+        });
+        old
+    }
+
+    /// Returns a tracked object matching `predicate`, if any.
+    ///
+    /// The inventory stops looking as soon as a matching object is found.
+    /// The order in which objects are visited is unspecified, so that the
+    /// returned object is the first one encountered, not necessarily the
+    /// first one tracked. `predicate` is called while holding the inventory lock.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = (0..10).map(|i| inventory.track(i)).collect();
+    /// assert_eq!(inventory.find(|&i| i > 7).map(|obj| *obj > 7), Some(true));
+    /// assert!(inventory.find(|&i| i > 10).is_none());
+    /// ```
+    pub fn find<P: Fn(&T) -> bool>(&self, predicate: P) -> Option<TrackedObject<T>> {
+        self.visit(|obj| {
+            if predicate(obj) {
+                ControlFlow::Break(TrackedObject {
+                    inner: obj.inner.clone(),
+                })
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+    }
+
+    /// Takes a snapshot of at most `max` tracked objects matching `predicate`.
+    ///
+    /// The inventory stops looking for matching objects as soon as `max` of
+    /// them have been found. `predicate` is called while holding the inventory lock.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = (0..100).map(|i| inventory.track(i)).collect();
+    /// let evens = inventory.find_up_to(|&i| i % 2 == 0, 3);
+    /// assert_eq!(evens.len(), 3);
+    /// assert!(evens.iter().all(|obj| **obj % 2 == 0));
+    /// ```
+    pub fn find_up_to<P: Fn(&T) -> bool>(&self, predicate: P, max: usize) -> Vec<TrackedObject<T>> {
+        let mut found = Vec::new();
+        if max == 0 {
+            return found;
+        }
+        self.visit(|obj| {
+            if predicate(obj) {
+                found.push(TrackedObject {
+                    inner: obj.inner.clone(),
+                });
+                if found.len() >= max {
+                    return ControlFlow::Break(());
+                }
+            }
+            ControlFlow::Continue(())
+        });
+        found
+    }
+
+    /// Takes a snapshot of the objects that have at least `min_strong` handles.
+    ///
+    /// The handles are counted before the snapshot is taken, so the
+    /// snapshot itself does not count, but previous snapshots still alive do.
+    /// This helps finding objects that are shared more than expected,
+    /// and therefore are not dropped.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let one = inventory.track(1);
+    /// let _two = inventory.track(2);
+    /// let _one_clone = one.clone();
+    /// let shared = inventory.shared_objects(2);
+    /// assert_eq!(shared.len(), 1);
+    /// assert_eq!(*shared[0], 1);
+    /// ```
+    pub fn shared_objects(&self, min_strong: usize) -> Vec<TrackedObject<T>> {
+        self.read_items()
+            .items
+            .iter()
+            .filter(|weak| weak.strong_count() >= min_strong.max(1))
+            .flat_map(WeakHandle::upgrade)
+            .map(|inner| TrackedObject { inner })
+            .collect()
+    }
+
+    /// Returns the `k` objects with the highest keys, by decreasing key.
+    ///
+    /// The key of an object is computed by `key_fn`, which is called while
+    /// holding the inventory lock. The objects are selected in a single scan,
+    /// without sorting the whole inventory.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = (0..10).map(|i| inventory.track(i)).collect();
+    /// let top: Vec<i32> = inventory.top_k_by_key(2, |&i| i % 7).iter().map(|obj| **obj).collect();
+    /// assert_eq!(top, vec![6, 5]);
+    /// ```
+    pub fn top_k_by_key<K: Ord, F: Fn(&T) -> K>(
+        &self,
+        k: usize,
+        key_fn: F,
+    ) -> Vec<TrackedObject<T>> {
+        if k == 0 {
+            return Vec::new();
+        }
+        // Min-heap of the best keys found so far, with the position of their object.
+        let mut heap: BinaryHeap<Reverse<(K, usize)>> = BinaryHeap::with_capacity(k + 1);
+        let mut candidates: BTreeMap<usize, TrackedObject<T>> = BTreeMap::new();
+        // Evicted candidates may be the last handle of their object:
+        // they are only dropped once the lock is released.
+        let mut evicted = Vec::new();
+        let mut pos = 0;
+        self.visit::<(), _>(|obj| {
+            heap.push(Reverse((key_fn(obj), pos)));
+            let candidate = TrackedObject {
+                inner: obj.inner.clone(),
+            };
+            candidates.insert(pos, candidate);
+            if heap.len() > k {
+                if let Some(Reverse((_, evicted_pos))) = heap.pop() {
+                    evicted.extend(candidates.remove(&evicted_pos));
+                }
+            }
+            pos += 1;
+            ControlFlow::Continue(())
+        });
+        drop(evicted);
+        heap.into_sorted_vec()
+            .into_iter()
+            .flat_map(|Reverse((_, pos))| candidates.remove(&pos))
+            .collect()
+    }
+
+    /// Takes a snapshot of a sample of at most `n` tracked objects.
+    ///
+    /// The sample is spread evenly over the inventory.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = (0..100).map(|i| inventory.track(i)).collect();
+    /// assert_eq!(inventory.sample(10).len(), 10);
+    /// ```
+    pub fn sample(&self, n: usize) -> Vec<TrackedObject<T>> {
+        if n == 0 {
+            return Vec::new();
+        }
+        let items = self.read_items();
+        let step = (items.alive_count() / n).max(1);
+        // Dead weak references are skipped before upgrading, so that
+        // no upgraded object is ever dropped while holding the lock.
+        items
+            .items
+            .iter()
+            .filter(|weak| weak.strong_count() > 0)
+            .step_by(step)
+            .flat_map(|weak| weak.upgrade())
+            .map(|inner| TrackedObject { inner })
+            .take(n)
+            .collect()
+    }
+
+    /// Returns the id, the age and a clone of the value of a sample of
+    /// at most `n` tracked objects.
+    ///
+    /// See `sample`.
+    #[cfg(feature = "std")]
+    pub fn profile_sample(&self, n: usize) -> Vec<(u64, Duration, T)>
+    where
+        T: Clone,
+    {
+        self.sample(n)
+            .into_iter()
+            .map(|obj| (obj.id(), obj.age(), T::clone(&obj)))
+            .collect()
+    }
+
+    /// Writes one line per living object to `w`, with the object id
+    /// followed by the `Debug` representation of its value.
+    ///
+    /// Lines are written directly to `w` from a snapshot of the inventory,
+    /// and `w` is flushed every 1024 lines and at the end of the dump.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let _one = inventory.track("one");
+    /// let mut dump = Vec::new();
+    /// inventory.write_dump(&mut dump).unwrap();
+    /// assert_eq!(String::from_utf8(dump).unwrap(), "0 \"one\"\n");
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn write_dump<W: io::Write>(&self, w: &mut W) -> io::Result<()>
+    where
+        T: fmt::Debug,
+    {
+        for (i, obj) in self.list().into_iter().enumerate() {
+            writeln!(w, "{} {:?}", obj.id(), *obj)?;
+            if (i + 1) % DUMP_FLUSH_INTERVAL == 0 {
+                w.flush()?;
+            }
+        }
+        w.flush()
+    }
+
+    /// Returns a map associating the id of each living object to the id
+    /// of the object it was created from using `map`, if any.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let one = inventory.track(1);
+    /// let two = one.map(|i| i + 1);
+    /// let lineage = inventory.lineage();
+    /// assert_eq!(lineage[&one.id()], None);
+    /// assert_eq!(lineage[&two.id()], Some(one.id()));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn lineage(&self) -> HashMap<u64, Option<u64>> {
+        let mut lineage = HashMap::new();
+        self.visit::<(), _>(|obj| {
+            lineage.insert(obj.id(), obj.parent_id());
+            ControlFlow::Continue(())
+        });
+        lineage
+    }
+
+    /// Calls `f` on each living object.
+    ///
+    /// Contrary to `list`, no snapshot is allocated: the objects are borrowed
+    /// one at a time.
+    ///
+    /// `f` is called while holding the inventory lock:
+    /// it must not call any method of the inventory, or it will deadlock.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = (1..=4).map(|i| inventory.track(i)).collect();
+    /// let mut sum = 0;
+    /// inventory.for_each(|&i| sum += i);
+    /// assert_eq!(sum, 10);
+    /// ```
+    pub fn for_each<F: FnMut(&T)>(&self, mut f: F) {
+        self.visit::<(), _>(|obj| {
+            f(obj);
+            ControlFlow::Continue(())
+        });
+    }
+
+    /// Calls `f` on each living object, until `f` returns `ControlFlow::Break`.
+    ///
+    /// Returns the payload of the `Break`, or `None` if `f` was called
+    /// on all of the living objects. Contrary to `list`, no snapshot is built.
+    ///
+    /// `f` is called while holding the inventory lock:
+    /// it must not call any method of the inventory.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    /// use std::ops::ControlFlow;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = (1..10).map(|i| inventory.track(i)).collect();
+    /// let square = inventory.try_for_each(|&i| {
+    ///     if i == 7 {
+    ///         ControlFlow::Break(i * i)
+    ///     } else {
+    ///         ControlFlow::Continue(())
+    ///     }
+    /// });
+    /// assert_eq!(square, Some(49));
+    /// ```
+    pub fn try_for_each<B, F: FnMut(&T) -> ControlFlow<B>>(&self, mut f: F) -> Option<B> {
+        self.visit(|obj| f(obj))
+    }
+
+    /// Takes a snapshot of the tracked objects, grouped by cohorts of age.
+    ///
+    /// The objects are keyed by `age / band`: the objects of cohort 0 were
+    /// tracked less than `band` ago, those of cohort 1 between `band` and
+    /// twice `band` ago, etc.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `band` is zero.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    /// use std::time::Duration;
+    ///
+    /// let inventory = Inventory::new();
+    /// let _one = inventory.track(1);
+    /// let cohorts = inventory.cohorts(Duration::from_secs(3600));
+    /// assert_eq!(cohorts[&0].len(), 1);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn cohorts(&self, band: Duration) -> BTreeMap<u64, Vec<TrackedObject<T>>> {
+        assert!(!band.is_zero(), "The cohort band must not be zero.");
+        let mut cohorts: BTreeMap<u64, Vec<TrackedObject<T>>> = BTreeMap::new();
+        for obj in self.list() {
+            let cohort = (obj.age().as_nanos() / band.as_nanos()) as u64;
+            cohorts.entry(cohort).or_default().push(obj);
+        }
+        cohorts
+    }
+
+    /// Returns the distribution of the ages of the living objects.
+    ///
+    /// `buckets` are the upper bounds (exclusive) of the buckets, in
+    /// increasing order. The returned vector has one count per bucket,
+    /// followed by the count of objects older than the last bound.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    /// use std::time::Duration;
+    ///
+    /// let inventory = Inventory::new();
+    /// let _one = inventory.track(1);
+    /// let histogram = inventory.age_histogram(&[Duration::from_secs(3600)]);
+    /// assert_eq!(histogram, vec![1, 0]);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn age_histogram(&self, buckets: &[Duration]) -> Vec<usize> {
+        let mut histogram = vec![0; buckets.len() + 1];
+        self.visit::<(), _>(|obj| {
+            let age = obj.age();
+            let bucket = buckets
+                .iter()
+                .position(|&bound| age < bound)
+                .unwrap_or(buckets.len());
+            histogram[bucket] += 1;
+            ControlFlow::Continue(())
+        });
+        histogram
+    }
+
+    /// Counts the living objects per key.
+    ///
+    /// No handle outlives the call, so that counting does not extend the
+    /// life of the objects. `key_fn` is called while holding the inventory lock.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = ["a", "b", "a"].iter().map(|&s| inventory.track(s)).collect();
+    /// let counts = inventory.count_by(|&s| s);
+    /// assert_eq!(counts["a"], 2);
+    /// assert_eq!(counts["b"], 1);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn count_by<K, F>(&self, key_fn: F) -> HashMap<K, usize>
+    where
+        K: Eq + Hash,
+        F: Fn(&T) -> K,
+    {
+        let mut counts: HashMap<K, usize> = HashMap::new();
+        self.visit::<(), _>(|obj| {
+            *counts.entry(key_fn(obj)).or_insert(0) += 1;
+            ControlFlow::Continue(())
+        });
+        counts
+    }
+
+    /// Counts the living objects per key, and returns the `top` keys
+    /// with the most objects, by decreasing count.
+    ///
+    /// Keys with the same count are ordered by increasing key.
+    /// `key_fn` is called while holding the inventory lock.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = ["a", "b", "a", "c", "a", "b"]
+    ///     .iter()
+    ///     .map(|&s| inventory.track(s))
+    ///     .collect();
+    /// assert_eq!(inventory.leaderboard(|&s| s, 2), vec![("a", 3), ("b", 2)]);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn leaderboard<K, F>(&self, key_fn: F, top: usize) -> Vec<(K, usize)>
+    where
+        K: Eq + Hash + Ord,
+        F: Fn(&T) -> K,
+    {
+        let mut leaderboard: Vec<(K, usize)> = self.count_by(key_fn).into_iter().collect();
+        leaderboard.sort_by(|(left_key, left_count), (right_key, right_count)| {
+            right_count
+                .cmp(left_count)
+                .then_with(|| left_key.cmp(right_key))
+        });
+        leaderboard.truncate(top);
+        leaderboard
+    }
+
+    /// Applies `f` to each living object, and collects the `Some` results.
+    ///
+    /// This is the canonical way to export data out of the inventory:
+    /// contrary to `list`, no handle escapes the call, so the objects'
+    /// lives are not extended.
+    ///
+    /// `f` is called while holding the inventory lock:
+    /// it must not call any method of the inventory.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = (0..5).map(|i| inventory.track(i)).collect();
+    /// let evens = inventory.collect_values(|&i| if i % 2 == 0 { Some(i.to_string()) } else { None });
+    /// assert_eq!(evens, vec!["0", "2", "4"]);
+    /// ```
+    pub fn collect_values<U, F: Fn(&T) -> Option<U>>(&self, f: F) -> Vec<U> {
+        let mut values = Vec::new();
+        self.visit::<(), _>(|obj| {
+            values.extend(f(obj));
+            ControlFlow::Continue(())
+        });
+        values
+    }
+
+    /// Calls `on_expire` on each object that has been tracked for at least `ttl`,
+    /// and returns the number of such objects.
+    ///
+    /// The inventory does not untrack expired objects by itself: `on_expire`
+    /// is where the caller can signal their owners to release them.
+    ///
+    /// The objects are looked up on a snapshot, so that `on_expire` is
+    /// called without holding the inventory lock.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    /// use std::time::Duration;
+    ///
+    /// let inventory = Inventory::new();
+    /// let _one = inventory.track(1);
+    /// let mut expired = Vec::new();
+    /// assert_eq!(inventory.sweep_expired(Duration::ZERO, |&i| expired.push(i)), 1);
+    /// assert_eq!(expired, vec![1]);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn sweep_expired<F: FnMut(&T)>(&self, ttl: Duration, mut on_expire: F) -> usize {
+        let mut num_expired = 0;
+        for obj in self.list() {
+            if obj.age() >= ttl {
+                on_expire(&obj);
+                num_expired += 1;
+            }
+        }
+        num_expired
+    }
+
+    /// Takes a snapshot of the tracked objects.
+    ///
+    /// This is the same snapshot as `list`, wrapped in a type that
+    /// can be iterated over.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let _one = inventory.track(1);
+    /// let snapshot = inventory.snapshot();
+    /// assert_eq!(snapshot.len(), 1);
+    /// for obj in snapshot {
+    ///     assert_eq!(*obj, 1);
+    /// }
+    /// ```
+    pub fn snapshot(&self) -> Snapshot<T> {
+        Snapshot(self.list())
+    }
+
+    /// Returns an iterator over the living objects.
+    ///
+    /// The objects are snapshotted eagerly, as with `list`, and are then
+    /// yielded one at a time, so that the iterator can be chained with
+    /// adapters. Each object is released as soon as it is dropped by
+    /// the caller, rather than when the whole snapshot is dropped.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs: Vec<_> = (0..10).map(|i| inventory.track(i)).collect();
+    /// assert_eq!(inventory.iter().filter(|obj| **obj % 2 == 0).count(), 5);
+    /// ```
+    pub fn iter(&self) -> SnapshotIter<T> {
+        self.snapshot().into_iter()
+    }
+
+    /// Returns an iterator over clones of the values of the living objects.
+    ///
+    /// The iterator is built on top of a snapshot, but each tracked object is
+    /// released as soon as its value has been yielded, so that iterating does
+    /// not pin the snapshot's objects any longer than necessary.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let _one = inventory.track(1);
+    /// let _two = inventory.track(2);
+    /// assert_eq!(inventory.values_iter().sum::<i32>(), 3);
+    /// ```
+    pub fn values_iter(&self) -> impl Iterator<Item = T>
+    where
+        T: Clone,
+    {
+        self.list().into_iter().map(|obj| T::clone(&obj))
+    }
+
+    /// Returns a serializable report of the inventory counters
+    /// and of its living objects.
+    ///
+    /// The counters and the list of objects are a consistent snapshot,
+    /// captured under a single lock acquisition.
+    #[cfg(feature = "serde")]
+    pub fn to_report(&self) -> CensusReport<T>
+    where
+        T: Clone + Serialize,
+    {
+        let (stats, objects) = {
+            let items = self.read_items();
+            (items.stats(), items.list_arc())
+        };
+        let objects = objects
+            .iter()
+            .map(|obj| ObjectReport {
+                id: obj.id(),
+                age_ms: obj.age().as_millis() as u64,
+                value: T::clone(obj),
+            })
+            .collect();
+        CensusReport {
+            name: self.inner.name.clone(),
+            alive: stats.alive,
+            peak: stats.peak,
+            total_tracked: stats.total_tracked,
+            objects,
+        }
+    }
+
+    /// Returns a `tokio` watch receiver following the number of living objects.
+    ///
+    /// The receiver is updated on every birth and death. As with any
+    /// watch channel, consumers only observe the latest count.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let count = inventory.count_watch();
+    /// let _one = inventory.track(1);
+    /// assert_eq!(*count.borrow(), 1);
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn count_watch(&self) -> tokio::sync::watch::Receiver<usize> {
+        let mut items = self.lock_items();
+        let alive_count = items.alive_count();
+        items
+            .count_sender
+            .get_or_insert_with(|| tokio::sync::watch::channel(alive_count).0)
+            .subscribe()
+    }
+
+    /// Calls `f` on every living object, in parallel, using rayon.
+    ///
+    /// The function works on a snapshot of the inventory: the inventory lock
+    /// is released before any call to `f`.
+    #[cfg(feature = "rayon")]
+    pub fn par_for_each<F>(&self, f: F)
+    where
+        T: Send + Sync,
+        F: Fn(&T) + Sync + Send,
+    {
+        use rayon::prelude::*;
+        let snapshot = self.list();
+        snapshot.par_iter().for_each(|obj| f(obj));
+    }
+
+    /// Runs `f` and asserts that it did not leave any new object alive.
+    ///
+    /// This is a helper for tests guarding against leaks.
+    /// See `assert_growth_at_most` for details.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let len = inventory.assert_no_growth(|| {
+    ///     let tmp = inventory.track("tmp".to_string());
+    ///     tmp.len()
+    /// });
+    /// assert_eq!(len, 3);
+    /// ```
+    pub fn assert_no_growth<R, F: FnOnce() -> R>(&self, f: F) -> R
+    where
+        T: fmt::Debug,
+    {
+        self.assert_growth_at_most(0, f)
+    }
+
+    /// Runs `f` and asserts that the number of living objects did not grow
+    /// by more than `max_growth` in the process.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of living objects grew by more than `max_growth`.
+    /// The panic message lists the objects that appeared while running `f`
+    /// and are still alive.
+    pub fn assert_growth_at_most<R, F: FnOnce() -> R>(&self, max_growth: usize, f: F) -> R
+    where
+        T: fmt::Debug,
+    {
+        // Objects are told apart by id: the memory of the objects dying in `f`
+        // can be reused by the objects born in it.
+        let count_before = self.len();
+        let mut before = self.snapshot_ids();
+        before.sort_unstable();
+        let res = f();
+        let count_after = self.len();
+        if count_after > count_before + max_growth {
+            let leaked: Vec<TrackedObject<T>> = self
+                .list()
+                .into_iter()
+                .filter(|obj| before.binary_search(&obj.id()).is_err())
+                .collect();
+            let msg = format!(
+                "Inventory grew by {} objects (max allowed: {}). Leaked objects: {:?}{}",
+                count_after - count_before,
+                max_growth,
+                leaked,
+                leak_origins(&leaked)
+            );
+            drop(leaked);
+            panic!("{}", msg);
+        }
+        res
+    }
+
+    /// Runs `f` and returns its result, along with the number of objects
+    /// of the inventory that died while `f` was running.
+    ///
+    /// Objects dying in other threads during the call are counted too.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let one = inventory.track(1);
+    /// let two = inventory.track(2);
+    /// let ((), dropped) = inventory.count_drops_in(|| drop(one));
+    /// assert_eq!(dropped, 1);
+    /// ```
+    pub fn count_drops_in<R, F: FnOnce() -> R>(&self, f: F) -> (R, usize) {
+        let dropped_before = self.stats().total_dropped;
+        let res = f();
+        let dropped_after = self.stats().total_dropped;
+        (res, (dropped_after - dropped_before) as usize)
+    }
+
+    /// This function blocks until there are no more items in the inventory.
+    ///
+    /// It is a helper calling
+    /// ```ignore
+    /// self.wait_until_predicate(|count| count == 0)
+    /// ```
+    ///
+    /// Note it is very easy to misuse this function and create a deadlock.
+    /// For instance, if any living TrackedObject is on the stack at the moment of the call,
+    /// it will not get dropped, and the inventory cannot become empty.
+    ///
+    /// This function also returns if the inventory gets closed.
+    #[cfg(feature = "std")]
+    pub fn wait_until_empty(&self) {
+        self.wait_until_predicate(|count| count == 0)
+    }
+
+    /// Blocks until at least `n` objects are alive in the inventory.
+    ///
+    /// This function also returns if the inventory gets closed.
+    #[cfg(feature = "std")]
+    pub fn wait_until_at_least(&self, n: usize) {
+        self.wait_until_predicate(|count| count >= n)
+    }
+
+    /// Blocks until at most `n` objects are alive in the inventory.
+    ///
+    /// As for `wait_until_empty`, the living objects held by the calling
+    /// thread are never dropped while it waits.
+    ///
+    /// This function also returns if the inventory gets closed.
+    #[cfg(feature = "std")]
+    pub fn wait_until_at_most(&self, n: usize) {
+        self.wait_until_predicate(|count| count <= n)
+    }
+
+    /// Blocks until `predicate` holds on a snapshot of the tracked objects,
+    /// and returns that snapshot.
+    ///
+    /// `predicate` is called without holding the inventory lock, on a fresh
+    /// snapshot every time the inventory changes. Snapshots that do not
+    /// satisfy the predicate are dropped before waiting again, so that they
+    /// do not keep their objects alive.
+    ///
+    /// This function also returns if the inventory gets closed, with the
+    /// last snapshot, whether it satisfies the predicate or not.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let _one = inventory.track(1);
+    /// let snapshot = inventory.wait_until_snapshot(|objs| objs.len() == 1);
+    /// assert_eq!(*snapshot[0], 1);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn wait_until_snapshot<F: Fn(&[TrackedObject<T>]) -> bool>(
+        &self,
+        predicate: F,
+    ) -> Vec<TrackedObject<T>> {
+        loop {
+            let (snapshot, version) = {
+                let items = self.read_items();
+                (items.list_arc(), items.version)
+            };
+            if predicate(&snapshot) || self.is_closed() {
+                return snapshot;
+            }
+            drop(snapshot);
+            let mut items = self.lock_items();
+            while items.version == version && !self.is_closed() {
+                items = self.wait_for_change(items, None);
+            }
+        }
+    }
+
+    /// This function blocks until there are no more items in the inventory,
+    /// calling `report` with the number of living objects every `interval`
+    /// in the meantime.
+    ///
+    /// `report` is called without holding the inventory lock.
+    /// As `wait_until_empty`, this function also returns if the inventory
+    /// gets closed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    /// use std::thread;
+    /// use std::time::Duration;
+    ///
+    /// let inventory = Inventory::new();
+    /// let one = inventory.track(1);
+    /// let handle = thread::spawn(move || {
+    ///     thread::sleep(Duration::from_millis(20));
+    ///     drop(one);
+    /// });
+    /// inventory.wait_until_empty_reporting(Duration::from_millis(5), |remaining| {
+    ///     println!("still waiting, {} remaining", remaining);
+    /// });
+    /// handle.join().unwrap();
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn wait_until_empty_reporting<F: FnMut(usize)>(&self, interval: Duration, mut report: F) {
+        assert!(
+            interval > Duration::ZERO,
+            "The reporting interval must not be zero."
+        );
+        let mut last_report = Instant::now();
+        let mut items = self.lock_items();
+        loop {
+            let count = items.alive_count();
+            if count == 0 || self.is_closed() {
+                return;
+            }
+            let elapsed = last_report.elapsed();
+            if elapsed >= interval {
+                drop(items);
+                report(count);
+                last_report = Instant::now();
+                items = self.lock_items();
+            } else {
+                items = self.wait_for_change(items, Some(interval - elapsed));
+            }
+        }
+    }
+
+    /// This function blocks until there are no more items in the inventory,
+    /// or until `f` asks to stop waiting.
+    ///
+    /// `f` is called with the number of living objects and the time elapsed
+    /// since the beginning of the wait, right away and then every time the
+    /// count changes, or at least every 100 milliseconds.
+    /// It is called without holding the inventory lock.
+    /// Waiting stops as soon as `f` returns `ControlFlow::Break`.
+    ///
+    /// Returns true if the inventory became empty, false if `f` stopped the wait.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    /// use std::ops::ControlFlow;
+    /// use std::time::Duration;
+    ///
+    /// let inventory = Inventory::new();
+    /// let _leaked = inventory.track(1);
+    /// let emptied = inventory.wait_until_empty_with(|_count, elapsed| {
+    ///     if elapsed >= Duration::from_millis(20) {
+    ///         ControlFlow::Break(())
+    ///     } else {
+    ///         ControlFlow::Continue(())
+    ///     }
+    /// });
+    /// assert!(!emptied);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn wait_until_empty_with<F: FnMut(usize, Duration) -> ControlFlow<()>>(
+        &self,
+        mut f: F,
+    ) -> bool {
+        let start = Instant::now();
+        let mut items = self.lock_items();
+        loop {
+            let count = items.alive_count();
+            if count == 0 {
+                return true;
+            }
+            drop(items);
+            if f(count, start.elapsed()).is_break() {
+                return false;
+            }
+            items = self.lock_items();
+            if items.alive_count() == count {
+                items = self.wait_for_change(items, Some(WAIT_CALLBACK_INTERVAL));
+            }
+        }
+    }
+
+    /// Spawns a thread calling `on_stuck` with the number of living objects
+    /// if the inventory is not empty by the time `deadline` elapses.
+    ///
+    /// The thread exits as soon as the inventory becomes empty or gets closed,
+    /// or right after calling `on_stuck`.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    /// use std::sync::mpsc::channel;
+    /// use std::time::Duration;
+    ///
+    /// let inventory = Inventory::new();
+    /// let _leaked = inventory.track(1);
+    /// let (send, recv) = channel();
+    /// let watchdog = inventory.spawn_watchdog(Duration::from_millis(10), move |count| {
+    ///     send.send(count).unwrap();
+    /// });
+    /// watchdog.join().unwrap();
+    /// assert_eq!(recv.recv().unwrap(), 1);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn spawn_watchdog<F>(&self, deadline: Duration, on_stuck: F) -> JoinHandle<()>
+    where
+        T: Send + Sync + 'static,
+        F: Fn(usize) + Send + 'static,
+    {
+        let inventory = self.clone();
+        thread::spawn(move || {
+            let outcome =
+                inventory.wait_until_predicate_ex(|count| count == 0, Some(deadline), None);
+            if outcome == WaitOutcome::TimedOut {
+                on_stuck(inventory.len());
+            }
+        })
+    }
+
+    /// This function blocks until the number of items in the repository matches a specific
+    /// predicate.
+    ///
+    /// See also `wait_until_empty`.
+    ///
+    /// This function also returns if the inventory gets closed,
+    /// whether the predicate is satisfied or not.
+    ///
+    /// Note it is very easy to misuse this function and create a deadlock.
+    /// For instance, if any living TrackedObject is on the stack at the moment of the call,
+    /// it will not get dropped, and the inventory cannot become empty.
+    #[cfg(feature = "std")]
+    pub fn wait_until_predicate<F: Fn(usize) -> bool>(&self, predicate_on_count: F) {
+        self.wait_until_predicate_ex(predicate_on_count, None, None);
+    }
+
+    /// Blocks until there are no more items in the inventory,
+    /// or until `timeout` elapses.
+    ///
+    /// Returns true if the inventory became empty, and false if the
+    /// timeout elapsed or the inventory got closed first.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    /// use std::time::Duration;
+    ///
+    /// let inventory = Inventory::new();
+    /// let _leaked = inventory.track(1);
+    /// assert!(!inventory.wait_until_empty_timeout(Duration::from_millis(10)));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn wait_until_empty_timeout(&self, timeout: Duration) -> bool {
+        self.wait_until_predicate_timeout(|count| count == 0, timeout)
+    }
+
+    /// Blocks until the number of items in the inventory matches a specific
+    /// predicate, or until `timeout` elapses.
+    ///
+    /// Returns true if the predicate was satisfied, and false if the
+    /// timeout elapsed or the inventory got closed first.
+    /// Spurious wakeups are handled: the predicate is checked again
+    /// after every wakeup, until the deadline.
+    #[cfg(feature = "std")]
+    pub fn wait_until_predicate_timeout<F: Fn(usize) -> bool>(
+        &self,
+        predicate_on_count: F,
+        timeout: Duration,
+    ) -> bool {
+        self.wait_until_predicate_ex(predicate_on_count, Some(timeout), None)
+            == WaitOutcome::Satisfied
+    }
+
+    /// Blocks until the number of items in the inventory matches a specific
+    /// predicate, the inventory is closed, the `timeout` elapses,
+    /// or the `cancel` flag is set.
+    ///
+    /// The predicate is checked first, then whether the inventory is closed,
+    /// then the cancellation flag, then the deadline.
+    /// Closing the inventory wakes up all of the waiting threads.
+    ///
+    /// Setting the `cancel` flag does not wake up the waiting thread:
+    /// the flag is polled every few milliseconds.
+    ///
+    /// ```rust
+    /// use census::{Inventory, WaitOutcome};
+    /// use std::time::Duration;
+    ///
+    /// let inventory = Inventory::new();
+    /// let _one = inventory.track(1);
+    /// let outcome = inventory.wait_until_predicate_ex(
+    ///     |count| count == 0,
+    ///     Some(Duration::from_millis(10)),
+    ///     None,
+    /// );
+    /// assert_eq!(outcome, WaitOutcome::TimedOut);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn wait_until_predicate_ex<F: Fn(usize) -> bool>(
+        &self,
+        predicate_on_count: F,
+        timeout: Option<Duration>,
+        cancel: Option<&AtomicBool>,
+    ) -> WaitOutcome {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut items = self.lock_items();
+        loop {
+            if predicate_on_count(items.alive_count()) {
+                return WaitOutcome::Satisfied;
+            }
+            if self.is_closed() {
+                return WaitOutcome::Closed;
+            }
+            if cancel.is_some_and(|cancel| cancel.load(Ordering::SeqCst)) {
+                return WaitOutcome::Cancelled;
+            }
+            let mut wait_duration = cancel.map(|_| CANCEL_POLL_INTERVAL);
+            if let Some(deadline) = deadline {
+                let now = Instant::now();
+                if now >= deadline {
+                    return WaitOutcome::TimedOut;
+                }
+                let remaining = deadline - now;
+                wait_duration = Some(wait_duration.map_or(remaining, |d| d.min(remaining)));
+            }
+            items = self.wait_for_change(items, wait_duration);
+        }
+    }
+
+    /// Returns the health of the inventory.
+    ///
+    /// This function never blocks: it does not acquire the inventory lock,
+    /// and works even if the lock is contended or poisoned.
+    /// As a result, `count` may be slightly stale.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let _one = inventory.track(1);
+    /// let health = inventory.health();
+    /// assert_eq!(health.count, 1);
+    /// assert!(health.is_healthy());
+    /// ```
+    pub fn health(&self) -> Health {
+        Health {
+            count: self.inner.alive.load(Ordering::Relaxed),
+            closed: self.is_closed(),
+            poisoned: sync::is_poisoned(&self.inner.items),
+        }
+    }
+
+    /// Closes the inventory.
+    ///
+    /// Objects alive at the moment of the call remain tracked,
+    /// but tracking new objects in a closed inventory panics.
+    ///
+    /// All of the threads waiting on the inventory are woken up,
+    /// and return without waiting for their predicate.
+    pub fn close(&self) {
+        let mut items = self.lock_items();
+        self.inner.closed.store(true, Ordering::SeqCst);
+        // Releases the channel of `collect_on_drain` if the inventory is empty.
+        let _ = items.drain_sender(true);
+        #[cfg(feature = "std")]
+        self.notify_waiters(&mut items);
+    }
+
+    /// Sets a callback called on every birth and death in the inventory.
+    ///
+    /// The observer is called right after a birth or a death was recorded,
+    /// without holding the inventory lock, so that it may call methods of the
+    /// inventory. Births are observed before `track` returns, and deaths
+    /// before the value of the object is dropped.
+    /// Births and deaths in child inventories are observed as well.
+    ///
+    /// The handle carried by a birth event keeps the object alive:
+    /// it should not be retained longer than necessary.
+    ///
+    /// ```rust
+    /// use census::{CensusEvent, Inventory};
+    /// use std::sync::mpsc::channel;
+    /// use std::sync::Mutex;
+    ///
+    /// let inventory = Inventory::new();
+    /// let (send, recv) = channel();
+    /// let send = Mutex::new(send);
+    /// inventory.set_observer(Box::new(move |event: CensusEvent<u32>| {
+    ///     send.lock().unwrap().send(matches!(event, CensusEvent::Birth { .. })).unwrap();
+    /// }));
+    /// drop(inventory.track(1));
+    /// assert_eq!(recv.try_iter().collect::<Vec<_>>(), vec![true, false]);
+    /// ```
+    pub fn set_observer(&self, observer: Box<dyn Fn(CensusEvent<T>) + Send + Sync>) {
+        self.lock_items().observer = Some(Arc::from(observer));
+    }
+
+    /// Returns a channel receiving the values of the objects of the inventory
+    /// as they die, instead of dropping them.
+    ///
+    /// A value is sent when the last handle of its object is dropped.
+    /// The values of objects untracked without being dropped, e.g. by `reclaim`,
+    /// are not sent. Objects that died before the call are not sent either.
+    ///
+    /// Once the inventory is closed and all of its objects died, the channel
+    /// gets disconnected, which makes it possible to collect all of the values
+    /// during a shutdown. Calling this function again replaces the channel.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let one = inventory.track(1);
+    /// let two = inventory.track(2);
+    /// let values = inventory.collect_on_drain();
+    /// inventory.close();
+    /// drop(two);
+    /// drop(one);
+    /// assert_eq!(values.iter().collect::<Vec<_>>(), vec![2, 1]);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn collect_on_drain(&self) -> Receiver<T> {
+        let (sender, receiver) = mpsc::channel();
+        let mut items = self.lock_items();
+        if !(self.is_closed() && items.alive_count() == 0) {
+            items.drain_sender = Some(sender);
+        }
+        receiver
+    }
+
+    /// Returns true if the inventory was closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner.closed.load(Ordering::SeqCst)
+    }
+
+    /// Starts tracking a given `T` object.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the inventory was closed, or if tracking the object
+    /// would exceed the inventory maximum or quota. See `try_track`.
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    pub fn track(&self, item: T) -> TrackedObject<T> {
+        self.track_with_parent(item, None)
+    }
+
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    fn track_with_parent(&self, item: T, parent_id: Option<u64>) -> TrackedObject<T> {
+        match self.try_track_with_parent(item, parent_id) {
+            Ok(tracked) => tracked,
+            Err(_) => panic!("Cannot track an object: the inventory quota is exceeded."),
+        }
+    }
+
+    /// Starts tracking a given `T` object, unless doing so would exceed
+    /// the inventory maximum or quota, in which case the object is handed back.
+    ///
+    /// The quota check and the registration of the object happen atomically.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the inventory was closed.
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    pub fn try_track(&self, item: T) -> Result<TrackedObject<T>, T> {
+        self.try_track_with_parent(item, None)
+    }
+
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    fn try_track_with_parent(
+        &self,
+        item: T,
+        parent_id: Option<u64>,
+    ) -> Result<TrackedObject<T>, T> {
+        match self.admit_and_register(item, parent_id) {
+            Ok(tracked) => Ok(tracked),
+            Err(Rejection::Closed(_)) => panic!("Cannot track an object in a closed inventory."),
+            Err(Rejection::OverQuota(item)) => Err(item),
+        }
+    }
+
+    /// Starts tracking a given `T` object, unless the inventory was closed,
+    /// in which case the object is handed back in the error.
+    ///
+    /// The check and the registration of the object happen atomically:
+    /// `close` cannot interleave between them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if tracking the object would exceed the inventory maximum or quota.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// inventory.close();
+    /// let err = inventory.checked_track(1).unwrap_err();
+    /// assert_eq!(err.into_inner(), 1);
+    /// ```
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    pub fn checked_track(&self, item: T) -> Result<TrackedObject<T>, ClosedError<T>> {
+        match self.admit_and_register(item, None) {
+            Ok(tracked) => Ok(tracked),
+            Err(Rejection::Closed(item)) => Err(ClosedError(item)),
+            Err(Rejection::OverQuota(_)) => {
+                panic!("Cannot track an object: the inventory quota is exceeded.")
+            }
+        }
+    }
+
+    /// Returns a handle to a living object equal to `item` if there is one,
+    /// or starts tracking `item` otherwise.
+    ///
+    /// The lookup and the registration of the object happen atomically, so
+    /// that concurrent calls with equal values never track two objects.
+    /// The lookup scans the living objects: it is linear in their number.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the object has to be tracked and the inventory was closed,
+    /// or if tracking the object would exceed the inventory maximum or quota.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let key = inventory.track_deduped("timeout");
+    /// let same_key = inventory.track_deduped("timeout");
+    /// assert!(key.ptr_eq(&same_key));
+    /// assert_eq!(inventory.len(), 1);
+    /// ```
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    pub fn track_deduped(&self, item: T) -> TrackedObject<T>
+    where
+        T: PartialEq,
+    {
+        // Declared before the lock, so that the objects for which the
+        // temporary handles were the last ones are dropped after the lock.
+        let mut orphans = Vec::new();
+        let items_lock = self.lock_items();
+        for obj in items_lock.living_objects() {
+            if *obj == item {
+                return obj;
+            }
+            if let Some(orphan) = Handle::into_inner(obj.inner) {
+                orphans.push(orphan);
+            }
+        }
+        match self.admit_and_register_locked(items_lock, item, None) {
+            Ok(tracked) => tracked,
+            Err(Rejection::Closed(_)) => panic!("Cannot track an object in a closed inventory."),
+            Err(Rejection::OverQuota(_)) => {
+                panic!("Cannot track an object: the inventory quota is exceeded.")
+            }
+        }
+    }
+
+    /// Checks that the inventory is open and that the object can be admitted,
+    /// and registers it.
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    fn admit_and_register(
+        &self,
+        item: T,
+        parent_id: Option<u64>,
+    ) -> Result<TrackedObject<T>, Rejection<T>> {
+        self.admit_and_register_locked(self.lock_items(), item, parent_id)
+    }
+
+    /// Same as `admit_and_register`, with the write lock on the items
+    /// already held by the caller. The lock is released before returning.
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    fn admit_and_register_locked(
+        &self,
+        mut items_lock: RwLockWriteGuard<'_, Items<T>>,
+        item: T,
+        parent_id: Option<u64>,
+    ) -> Result<TrackedObject<T>, Rejection<T>> {
+        if self.is_closed() {
+            return Err(Rejection::Closed(item));
+        }
+        if !items_lock.admit(&item) {
+            return Err(Rejection::OverQuota(item));
+        }
+        let alive_before = items_lock.direct_alive_count;
+        let tracked = self.register(&mut items_lock, item, parent_id);
+        let births = core::slice::from_ref(&tracked);
+        self.after_births(items_lock, alive_before, births);
+        self.register_in_ancestors(births);
+        Ok(tracked)
+    }
+
+    /// Starts tracking all of the given objects, under a single lock acquisition.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the inventory was closed, or if tracking one of the objects
+    /// would exceed the inventory maximum or quota.
+    /// In the latter case, the objects of the batch that were already
+    /// tracked are dropped.
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    fn track_batch_inner<I: IntoIterator<Item = T>>(
+        &self,
+        items: I,
+        parent_id: Option<u64>,
+    ) -> Vec<TrackedObject<T>> {
+        assert!(
+            !self.is_closed(),
+            "Cannot track an object in a closed inventory."
+        );
+        let mut tracked_objects = Vec::new();
+        let mut items_lock = self.lock_items();
+        let alive_before = items_lock.direct_alive_count;
+        for item in items {
+            if !items_lock.admit(&item) {
+                self.after_births(items_lock, alive_before, &tracked_objects);
+                self.register_in_ancestors(&tracked_objects);
+                drop(tracked_objects);
+                panic!("Cannot track an object: the inventory quota is exceeded.");
+            }
+            tracked_objects.push(self.register(&mut items_lock, item, parent_id));
+        }
+        self.after_births(items_lock, alive_before, &tracked_objects);
+        self.register_in_ancestors(&tracked_objects);
+        tracked_objects
+    }
+
+    /// Registers a new object. The object must have been admitted.
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    fn register(&self, items: &mut Items<T>, item: T, parent_id: Option<u64>) -> TrackedObject<T> {
+        let inner = InnerTrackedObject {
+            census: self.clone(),
+            id: self.root().inner.next_id.fetch_add(1, Ordering::Relaxed),
+            parent_id,
+            #[cfg(feature = "std")]
+            created_at: Instant::now(),
+            #[cfg(feature = "backtrace")]
+            tracked_at: Location::caller(),
+            item: ManuallyDrop::new(item),
+        };
+        let handle = match self.inner.slab {
+            Some(ref slab) => Handle::Slab(slab.insert(inner)),
+            None => Handle::Heap(Arc::new(inner)),
+        };
+        self.push_weak(items, Handle::downgrade(&handle));
+        items.direct_alive_count += 1;
+        TrackedObject { inner: handle }
+    }
+
+    /// Adds the reference to a new object to the internal vector,
+    /// and records its birth.
+    fn push_weak(&self, items: &mut Items<T>, weak: WeakHandle<InnerTrackedObject<T>>) {
+        if items.items.len() == items.items.capacity() {
+            if let Some(additional) = items.growth_policy.additional(items.items.capacity()) {
+                items.items.reserve_exact(additional);
+            }
+        }
+        items.items.push(weak);
+        items.record_birth();
+    }
+
+    /// Returns the inventory at the top of the hierarchy of this inventory.
+    fn root(&self) -> &Inventory<T> {
+        let mut inventory = self;
+        while let Some(parent) = inventory.inner.parent.as_ref() {
+            inventory = parent;
+        }
+        inventory
+    }
+
+    /// Registers objects tracked in this inventory in all of its ancestors.
+    ///
+    /// The ancestors are locked one at a time, after this inventory was
+    /// unlocked, so that no two inventories of a hierarchy are ever locked together.
+    fn register_in_ancestors(&self, objects: &[TrackedObject<T>]) {
+        let mut ancestor = self.inner.parent.as_ref();
+        while let Some(inventory) = ancestor {
+            let mut items = inventory.lock_items();
+            let alive_before = items.direct_alive_count;
+            for obj in objects {
+                inventory.push_weak(&mut items, Handle::downgrade(&obj.inner));
+            }
+            inventory.after_births(items, alive_before, objects);
+            ancestor = inventory.inner.parent.as_ref();
+        }
+    }
+
+    /// Records the deaths of objects tracked in this inventory
+    /// in all of its ancestors.
+    fn untrack_from_ancestors(&self, ids: &[u64]) {
+        let mut ancestor = self.inner.parent.as_ref();
+        while let Some(inventory) = ancestor {
+            let mut items = inventory.lock_items();
+            for _ in ids {
+                items.record_descendant_death();
+            }
+            inventory.after_deaths(items, ids);
+            ancestor = inventory.inner.parent.as_ref();
+        }
+    }
+
+    /// Publishes the births registered since the count of objects tracked
+    /// directly was `alive_before`, and releases the lock.
+    fn after_births(
+        &self,
+        mut items: RwLockWriteGuard<'_, Items<T>>,
+        alive_before: usize,
+        births: &[TrackedObject<T>],
+    ) {
+        self.publish_count(&mut items);
+        let alarm = items.crossed_capacity_alarm(alive_before);
+        let observer = items.observer.clone();
+        #[cfg(feature = "tracing")]
+        let alive = items.alive_count();
+        drop(items);
+        #[cfg(feature = "tracing")]
+        for obj in births {
+            tracing::trace!(id = obj.id(), alive, "object tracked");
+        }
+        if let Some((callback, alive, max_alive)) = alarm {
+            callback(alive, max_alive);
+        }
+        if let Some(observer) = observer {
+            for obj in births {
+                observer(CensusEvent::Birth {
+                    id: obj.id(),
+                    object: TrackedObject {
+                        inner: obj.inner.clone(),
+                    },
+                });
+            }
+        }
+    }
+
+    /// Publishes the deaths of the objects with the given ids,
+    /// and releases the lock.
+    fn after_deaths(&self, mut items: RwLockWriteGuard<'_, Items<T>>, ids: &[u64]) {
+        self.publish_count(&mut items);
+        let observer = items.observer.clone();
+        #[cfg(feature = "tracing")]
+        let alive = items.alive_count();
+        drop(items);
+        #[cfg(feature = "tracing")]
+        for &id in ids {
+            tracing::trace!(id, alive, "object dropped");
+        }
+        if let Some(observer) = observer {
+            for &id in ids {
+                observer(CensusEvent::Death { id });
+            }
+        }
+    }
+
+    /// Untracks a batch of objects of this inventory under a single
+    /// lock acquisition, and hands back their items, along with the
+    /// channel the items should be sent to if they are to be dropped.
+    fn untrack_batch(
+        &self,
+        objects: Vec<InnerTrackedObject<T>>,
+    ) -> (Vec<T>, Option<DrainSender<T>>) {
+        if objects.is_empty() {
+            return (Vec::new(), None);
+        }
+        let ids: Vec<u64> = objects.iter().map(|obj| obj.id).collect();
+        let mut items = self.lock_items();
+        for obj in &objects {
+            items.record_death(&obj.item);
+        }
+        self.inner
+            .death_seq
+            .fetch_add(objects.len() as u64, Ordering::Relaxed);
+        let drain_sender = items.drain_sender(self.is_closed());
+        self.after_deaths(items, &ids);
+        self.untrack_from_ancestors(&ids);
+        let values = objects
+            .into_iter()
+            .map(InnerTrackedObject::into_item_untracked)
+            .collect();
+        (values, drain_sender)
+    }
+
+    /// Reclaims the values of the given tracked objects.
+    ///
+    /// Only the objects for which the given handle is the last handle are
+    /// reclaimed: they are untracked, and their values are returned,
+    /// instead of being dropped. Objects with other living handles are simply
+    /// released, and remain tracked for as long as their other handles live.
+    ///
+    /// The deaths of the reclaimed objects of this inventory are recorded
+    /// under a single lock acquisition.
+    ///
+    /// The objects have to be handed over by the caller: there is no
+    /// `reclaim(self)` unwrapping the objects of a snapshot of the inventory,
+    /// as the inventory only holds weak references to its objects. A living
+    /// object always has a handle outside of the snapshot, so that the
+    /// snapshot would never be the sole owner of any of them. To collect all
+    /// of the values at shutdown, use `collect_on_drain` and `close` instead.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let one = inventory.track(1);
+    /// let two = inventory.track(2);
+    /// let two_clone = two.clone();
+    /// assert_eq!(inventory.reclaim(vec![one, two]), vec![1]);
+    /// assert_eq!(inventory.len(), 1);
+    /// ```
+    pub fn reclaim<I: IntoIterator<Item = TrackedObject<T>>>(&self, objects: I) -> Vec<T> {
+        let mut reclaimed = Vec::new();
+        let mut orphans = Vec::new();
+        for obj in objects {
+            if let Ok(inner) = Handle::try_unwrap(obj.inner) {
+                if Arc::ptr_eq(&inner.census.inner, &self.inner) {
+                    orphans.push(inner);
+                } else {
+                    reclaimed.push(inner.into_item());
+                }
+            }
+        }
+        reclaimed.extend(self.untrack_batch(orphans).0);
+        reclaimed
+    }
+
+    /// Starts tracking all of the given objects, and returns a `BatchHandle`
+    /// owning the resulting tracked objects.
+    ///
+    /// All of the objects are registered under a single lock acquisition.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the inventory was closed, or if tracking one of the objects
+    /// would exceed the inventory maximum or quota.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let mut batch = inventory.track_batch(vec![1, 2, 3]);
+    /// assert_eq!(inventory.len(), 3);
+    /// batch.release(1);
+    /// assert_eq!(inventory.len(), 2);
+    /// drop(batch);
+    /// assert_eq!(inventory.len(), 0);
+    /// ```
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    pub fn track_batch<I: IntoIterator<Item = T>>(&self, items: I) -> BatchHandle<T> {
+        BatchHandle {
+            objects: self
+                .track_batch_inner(items, None)
+                .into_iter()
+                .map(Some)
+                .collect(),
+        }
+    }
+
+    /// Starts tracking all of the given objects, and returns the resulting
+    /// tracked objects, in order.
+    ///
+    /// All of the objects are registered under a single lock acquisition,
+    /// and waiters are notified once for the whole batch.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the inventory was closed, or if tracking one of the objects
+    /// would exceed the inventory maximum or quota.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let objs = inventory.track_all(1..=3);
+    /// assert_eq!(objs.iter().map(|obj| **obj).collect::<Vec<_>>(), vec![1, 2, 3]);
+    /// ```
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    pub fn track_all<I: IntoIterator<Item = T>>(&self, items: I) -> Vec<TrackedObject<T>> {
+        self.track_batch_inner(items, None)
+    }
+
+    /// Converts `value` into a `T` and starts tracking it.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory: Inventory<String> = Inventory::new();
+    /// let one = inventory.track_into("one");
+    /// assert_eq!(*one, "one");
+    /// ```
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    pub fn track_into<U: Into<T>>(&self, value: U) -> TrackedObject<T> {
+        self.track(value.into())
+    }
+
+    /// Tracks `item` for the duration of `f`.
+    ///
+    /// `f` is given a borrowed handle to the tracked object. The handle
+    /// is dropped when `f` returns, so that the object gets untracked,
+    /// unless `f` cloned the handle.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let len = inventory.scoped(1, |_one| inventory.len());
+    /// assert_eq!(len, 1);
+    /// assert_eq!(inventory.len(), 0);
+    /// ```
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    pub fn scoped<R, F: FnOnce(&TrackedObject<T>) -> R>(&self, item: T, f: F) -> R {
+        let tracked = self.track(item);
+        f(&tracked)
+    }
+
+    /// Returns a scope, holding a handle to each of the objects tracked
+    /// through it until it is dropped.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let scope = inventory.scope();
+    /// scope.track(1);
+    /// scope.track(2);
+    /// assert_eq!(inventory.len(), 2);
+    /// drop(scope);
+    /// assert_eq!(inventory.len(), 0);
+    /// ```
+    pub fn scope(&self) -> CensusScope<T> {
+        CensusScope {
+            inventory: self.clone(),
+            objects: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Starts tracking a given `T` object, and returns its id
+    /// alongside the tracked object.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let (one, id) = inventory.track_with_id(1);
+    /// assert_eq!(one.id(), id);
+    /// ```
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    pub fn track_with_id(&self, item: T) -> (TrackedObject<T>, u64) {
+        let tracked = self.track(item);
+        let id = tracked.id();
+        (tracked, id)
+    }
+}
+
+/// Your tracked object.
+///
+/// A tracked object contains reference counting logic and an
+/// `Arc` to your object. It is cloneable but calling clone will
+/// not clone your internal object.
+///
+/// Your object cannot be mutated. You can borrow it using
+/// the `Deref` interface. Objects that need to be mutated
+/// can be tracked with `Inventory::track_mut`.
+#[derive(Clone)]
+pub struct TrackedObject<T> {
+    inner: Handle<InnerTrackedObject<T>>,
+}
+
+/// Describes where the leaked objects were tracked, if known.
+#[cfg(feature = "backtrace")]
+fn leak_origins<T>(leaked: &[TrackedObject<T>]) -> String {
+    let origins: Vec<String> = leaked
+        .iter()
+        .map(|obj| obj.tracked_at().to_string())
+        .collect();
+    format!(", tracked at: {:?}", origins)
+}
+
+#[cfg(not(feature = "backtrace"))]
+fn leak_origins<T>(_leaked: &[TrackedObject<T>]) -> String {
+    String::new()
+}
+
+struct InnerTrackedObject<T> {
+    census: Inventory<T>,
+    id: u64,
+    parent_id: Option<u64>,
+    #[cfg(feature = "std")]
+    created_at: Instant,
+    #[cfg(feature = "backtrace")]
+    tracked_at: &'static Location<'static>,
+    item: ManuallyDrop<T>,
+}
+
+impl<T> InnerTrackedObject<T> {
+    /// Records the death of the object, and returns the channel
+    /// its item should be sent to if it is to be dropped.
+    fn untrack(&self) -> Option<DrainSender<T>> {
+        let mut lock = self.census.lock_items();
+        lock.record_death(&self.item);
+        self.census.inner.death_seq.fetch_add(1, Ordering::Relaxed);
+        let drain_sender = lock.drain_sender(self.census.is_closed());
+        self.census.after_deaths(lock, &[self.id]);
+        self.census.untrack_from_ancestors(&[self.id]);
+        drain_sender
+    }
+
+    /// Untracks the object and hands back its item instead of dropping it.
+    fn into_item(self) -> T {
+        self.untrack();
+        self.into_item_untracked()
+    }
+
+    /// Hands back the item without recording the death of the object.
+    /// The caller is in charge of recording it.
+    fn into_item_untracked(self) -> T {
+        let mut this = ManuallyDrop::new(self);
+        // Safety: `this` is never dropped, so the item is moved out exactly once
+        // and every other field is dropped exactly once.
+        unsafe {
+            let item = ManuallyDrop::take(&mut this.item);
+            ptr::drop_in_place(&mut this.census);
+            item
+        }
+    }
+}
+
+/// A set of tracked objects created together by `Inventory::track_batch`.
+///
+/// The handle owns the tracked objects, which can be accessed by index
+/// and released individually. Dropping the handle releases all of the
+/// remaining objects, recording their deaths under a single lock acquisition.
+pub struct BatchHandle<T> {
+    objects: Vec<Option<TrackedObject<T>>>,
+}
+
+impl<T> BatchHandle<T> {
+    /// Returns the number of objects of the batch that were not released.
+    pub fn len(&self) -> usize {
+        self.objects.iter().filter(|obj| obj.is_some()).count()
+    }
+
+    /// Returns true if all of the objects of the batch were released.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the object at position `index` in the batch,
+    /// or `None` if it was released.
+    pub fn get(&self, index: usize) -> Option<&TrackedObject<T>> {
+        self.objects.get(index).and_then(Option::as_ref)
+    }
+
+    /// Releases the object at position `index` in the batch.
+    ///
+    /// Returns false if the object was already released.
+    pub fn release(&mut self, index: usize) -> bool {
+        self.objects.get_mut(index).and_then(Option::take).is_some()
+    }
+}
+
+impl<T> Drop for BatchHandle<T> {
+    fn drop(&mut self) {
+        let mut census = None;
+        let mut orphans = Vec::new();
+        for obj in self.objects.drain(..).flatten() {
+            census.get_or_insert_with(|| obj.inner.census.clone());
+            // Objects still referenced elsewhere are simply released here.
+            if let Some(orphan) = Handle::into_inner(obj.inner) {
+                orphans.push(orphan);
+            }
+        }
+        if let Some(census) = census {
+            let (values, drain_sender) = census.untrack_batch(orphans);
+            if let Some(drain_sender) = drain_sender {
+                for value in values {
+                    let _ = drain_sender.send(value);
+                }
+            }
+        }
+    }
+}
+
+/// A scope of tracked objects, created by `Inventory::scope`.
+///
+/// The scope holds a handle to each of the objects tracked through it.
+/// Dropping the scope releases these handles, recording the deaths of
+/// the objects that have no other handle under a single lock acquisition.
+/// The objects whose handles were cloned remain alive for as long as
+/// the clones do.
+pub struct CensusScope<T> {
+    inventory: Inventory<T>,
+    objects: Mutex<Vec<TrackedObject<T>>>,
+}
+
+impl<T> CensusScope<T> {
+    /// Starts tracking a given `T` object for the duration of the scope.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the inventory was closed, or if tracking the object
+    /// would exceed the inventory maximum or quota.
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    pub fn track(&self, item: T) -> TrackedObject<T> {
+        let tracked = self.inventory.track(item);
+        sync::lock(&self.objects).push(TrackedObject {
+            inner: tracked.inner.clone(),
+        });
+        tracked
+    }
+
+    /// Returns the number of objects tracked through the scope.
+    pub fn len(&self) -> usize {
+        sync::lock(&self.objects).len()
+    }
+
+    /// Returns true if no object was tracked through the scope.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Drop for CensusScope<T> {
+    fn drop(&mut self) {
+        let objects = mem::take(sync::get_mut(&mut self.objects));
+        drop(BatchHandle {
+            objects: objects.into_iter().map(Some).collect(),
+        });
+    }
+}
+
+/// A tracked object that can be mutated, created by `Inventory::track_mut`.
+///
+/// The object is stored behind a read-write lock, in an inventory of
+/// `RwLock<T>`. It is counted and listed like any other tracked object,
+/// and all of its clones see the same value.
+#[cfg(feature = "std")]
+pub struct TrackedMut<T> {
+    object: TrackedObject<std::sync::RwLock<T>>,
+}
+
+#[cfg(feature = "std")]
+impl<T> TrackedMut<T> {
+    /// Locks the object for reading.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a thread panicked while holding the write lock.
+    pub fn read(&self) -> std::sync::RwLockReadGuard<'_, T> {
+        self.object.read().unwrap()
+    }
+
+    /// Locks the object for writing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a thread panicked while holding the write lock.
+    pub fn write(&self) -> std::sync::RwLockWriteGuard<'_, T> {
+        self.object.write().unwrap()
+    }
+
+    /// Locks the object exclusively, as a `Mutex` would.
+    ///
+    /// This is the same as `write`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a thread panicked while holding the write lock.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let session = inventory.track_mut(Vec::new());
+    /// session.lock().push("login");
+    /// assert_eq!(*session.read(), vec!["login"]);
+    /// ```
+    pub fn lock(&self) -> std::sync::RwLockWriteGuard<'_, T> {
+        self.write()
+    }
+
+    /// Returns the underlying tracked object.
+    pub fn as_tracked(&self) -> &TrackedObject<std::sync::RwLock<T>> {
+        &self.object
+    }
+
+    /// Returns the id of the object.
+    pub fn id(&self) -> u64 {
+        self.object.id()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Clone for TrackedMut<T> {
+    fn clone(&self) -> Self {
+        TrackedMut {
+            object: TrackedObject {
+                inner: self.object.inner.clone(),
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Inventory<std::sync::RwLock<T>> {
+    /// Starts tracking a given `T` object that can be mutated through
+    /// the returned handle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the inventory was closed, or if tracking the object
+    /// would exceed the inventory maximum or quota.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let counter = inventory.track_mut(0);
+    /// *counter.write() += 1;
+    /// assert_eq!(*inventory.list()[0].read().unwrap(), 1);
+    /// ```
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    pub fn track_mut(&self, item: T) -> TrackedMut<T> {
+        TrackedMut {
+            object: self.track(std::sync::RwLock::new(item)),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for TrackedObject<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "Tracked({:?})", *self.inner.item)
+    }
+}
+
+impl<T> TrackedObject<T> {
+    /// Returns the id of the object.
+    ///
+    /// Ids are assigned in increasing order when objects are tracked.
+    /// They are unique within an inventory for its whole lifetime,
+    /// and are shared by all of the clones of a tracked object.
+    /// Contrary to addresses, they are never reused, which makes them
+    /// suitable to correlate births and deaths in logs.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let one = inventory.track(1);
+    /// let two = one.map(|i| i + 1);
+    /// assert_eq!(one.clone().id(), one.id());
+    /// assert_ne!(two.id(), one.id());
+    /// ```
+    pub fn id(&self) -> u64 {
+        self.inner.id
+    }
+
+    /// Returns the id of the object this object was created from
+    /// using `map`, if any.
+    pub fn parent_id(&self) -> Option<u64> {
+        self.inner.parent_id
+    }
+
+    /// Returns true if both handles point to the same object.
+    ///
+    /// Contrary to comparing the values, this tests the identity
+    /// of the objects, and does not require `T: PartialEq`.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let one = inventory.track(1);
+    /// let other_one = inventory.track(1);
+    /// assert!(one.ptr_eq(&one.clone()));
+    /// assert!(!one.ptr_eq(&other_one));
+    /// ```
+    pub fn ptr_eq(&self, other: &TrackedObject<T>) -> bool {
+        Handle::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// Returns the time at which the object was tracked.
+    ///
+    /// Clones of a tracked object share its creation time, while
+    /// objects created with `map` get their own.
+    #[cfg(feature = "std")]
+    pub fn created_at(&self) -> Instant {
+        self.inner.created_at
+    }
+
+    /// Returns the time elapsed since the object was tracked.
+    #[cfg(feature = "std")]
+    pub fn age(&self) -> Duration {
+        self.inner.created_at.elapsed()
+    }
+
+    /// Returns the source location of the call that started tracking
+    /// the object, e.g. the `track` or `map` call.
+    ///
+    /// Requires the `backtrace` feature.
+    #[cfg(feature = "backtrace")]
+    pub fn tracked_at(&self) -> &'static Location<'static> {
+        self.inner.tracked_at
+    }
+
+    /// Creates a new object from an existing one.
+    ///
+    /// The new object will be registered
+    /// in your original object's inventory.
+    ///
+    /// ```rust
+    /// use census::{Inventory, TrackedObject};
+    ///
+    /// let inventory = Inventory::new();
+    ///
+    /// let seven = inventory.track(7);
+    /// let fourteen = seven.map(|i| i * 2);
+    /// assert_eq!(*fourteen, 14);
+    ///
+    /// let living_instances = inventory.list();
+    /// assert_eq!(living_instances.len(), 2);
+    /// ```
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    pub fn map<F>(&self, f: F) -> TrackedObject<T>
+    where
+        F: FnOnce(&T) -> T,
+    {
+        let t = f(self);
+        self.inner.census.track_with_parent(t, Some(self.id()))
+    }
+
+    /// Creates a new object from an existing one, if `f` succeeds.
+    ///
+    /// The new object is registered in the original object's inventory.
+    /// If `f` fails, its error is returned and the inventory is left untouched.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let text = inventory.track("7".to_string());
+    /// let parsed = text.try_map(|s| s.parse::<u32>().map(|i| (i * 2).to_string()));
+    /// assert_eq!(*parsed.unwrap(), "14");
+    /// assert!(text.try_map(|s| s.parse::<bool>().map(|b| b.to_string())).is_err());
+    /// assert_eq!(inventory.len(), 1);
+    /// ```
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    pub fn try_map<F, E>(&self, f: F) -> Result<TrackedObject<T>, E>
+    where
+        F: FnOnce(&T) -> Result<T, E>,
+    {
+        let t = f(self)?;
+        Ok(self.inner.census.track_with_parent(t, Some(self.id())))
+    }
+
+    /// Creates a new object of another type from an existing one,
+    /// and tracks it in `target`.
+    ///
+    /// Contrary to `map`, the new object lives in `target`, not in
+    /// the inventory of `self`, and it has no parent id.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let numbers = Inventory::new();
+    /// let strings = Inventory::new();
+    /// let seven = numbers.track(7u32);
+    /// let seven_str = seven.map_into(&strings, |i| i.to_string());
+    /// assert_eq!(*seven_str, "7");
+    /// assert_eq!(strings.len(), 1);
+    /// ```
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    pub fn map_into<U, F>(&self, target: &Inventory<U>, f: F) -> TrackedObject<U>
+    where
+        F: FnOnce(&T) -> U,
+    {
+        target.track(f(self))
+    }
+
+    /// Creates several new objects from an existing one.
+    ///
+    /// The new objects are registered in your original object's inventory,
+    /// under a single lock acquisition.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let six = inventory.track(6);
+    /// let halves = six.fan_out(|&i| vec![i / 2, i / 2]);
+    /// assert_eq!(*halves[0], 3);
+    /// assert_eq!(inventory.len(), 3);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the inventory was closed, or if tracking one of the
+    /// new objects would exceed the inventory maximum or quota.
+    #[cfg_attr(feature = "backtrace", track_caller)]
+    pub fn fan_out<F>(&self, f: F) -> Vec<TrackedObject<T>>
+    where
+        F: Fn(&T) -> Vec<T>,
+    {
+        let values = f(self);
+        self.inner.census.track_batch_inner(values, Some(self.id()))
+    }
+
+    /// Moves the object to another inventory.
+    ///
+    /// The object is untracked from its original inventory and
+    /// tracked in `dest`. The returned object is a new object:
+    /// it does not share its identity with `self`.
+    ///
+    /// If `self` is the last handle to the object, its value is moved
+    /// to `dest` without being cloned.
+    /// Otherwise the value is cloned into `dest`, and the original
+    /// object remains tracked by its inventory for as long as
+    /// the other handles are alive.
+    /// Objects that cannot be cloned can be moved with `try_move_to`.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let source = Inventory::new();
+    /// let dest = Inventory::new();
+    ///
+    /// let one = source.track(1);
+    /// let one = one.move_to(&dest);
+    /// assert_eq!(*one, 1);
+    /// assert_eq!(source.len(), 0);
+    /// assert_eq!(dest.len(), 1);
+    /// ```
+    pub fn move_to(self, dest: &Inventory<T>) -> TrackedObject<T>
+    where
+        T: Clone,
+    {
+        match Handle::try_unwrap(self.inner) {
+            Ok(inner) => dest.track(inner.into_item()),
+            Err(inner) => dest.track(T::clone(&inner.item)),
+        }
+    }
+
+    /// Moves the object to another inventory, if `self` is the last handle
+    /// to the object. Otherwise, `self` is handed back unchanged.
+    ///
+    /// Contrary to `move_to`, this does not require `T: Clone`.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let source = Inventory::new();
+    /// let dest = Inventory::new();
+    ///
+    /// let file = source.track(std::io::empty());
+    /// let file = file.try_move_to(&dest).ok().unwrap();
+    /// assert_eq!(source.len(), 0);
+    /// assert_eq!(dest.len(), 1);
+    /// ```
+    pub fn try_move_to(self, dest: &Inventory<T>) -> Result<TrackedObject<T>, TrackedObject<T>> {
+        self.try_into_inner().map(|item| dest.track(item))
+    }
+
+    /// Untracks the object and returns its value, if `self` is the last
+    /// handle to the object. Otherwise, `self` is handed back unchanged.
+    ///
+    /// As with `Arc::try_unwrap`, if two threads concurrently try to
+    /// unwrap the last two handles of an object, both can fail.
+    ///
+    /// ```rust
+    /// use census::Inventory;
+    ///
+    /// let inventory = Inventory::new();
+    /// let buffer = inventory.track(vec![0u8; 1024]);
+    /// let buffer_clone = buffer.clone();
+    /// let buffer = buffer.try_into_inner().unwrap_err();
+    /// drop(buffer_clone);
+    /// assert_eq!(buffer.try_into_inner().unwrap().len(), 1024);
+    /// assert!(inventory.is_empty());
+    /// ```
+    pub fn try_into_inner(self) -> Result<T, TrackedObject<T>> {
+        match Handle::try_unwrap(self.inner) {
+            Ok(inner) => Ok(inner.into_item()),
+            Err(inner) => Err(TrackedObject { inner }),
+        }
+    }
+}
+
+impl<T> Drop for InnerTrackedObject<T> {
+    fn drop(&mut self) {
+        // The death is recorded before dropping the item, so that
+        // the inventory stays consistent even if the item's `Drop` panics.
+        match self.untrack() {
+            Some(drain_sender) => {
+                // Safety: the item is moved out here and nowhere else.
+                let item = unsafe { ManuallyDrop::take(&mut self.item) };
+                // If the receiver is gone, the item is simply dropped.
+                let _ = drain_sender.send(item);
+            }
+            // Safety: the item is dropped here and nowhere else.
+            None => unsafe { ManuallyDrop::drop(&mut self.item) },
+        }
+    }
+}
+
+/// A tracked object serializes as its value.
+///
+/// ```rust
+/// # extern crate serde_json;
+/// # extern crate census;
+/// use census::Inventory;
+///
+/// let inventory = Inventory::new();
+/// let _one = inventory.track("one".to_string());
+/// let json = serde_json::to_string(&inventory.list()).unwrap();
+/// assert_eq!(json, r#"["one"]"#);
+/// ```
+#[cfg(feature = "serde")]
+impl<T: Serialize> Serialize for TrackedObject<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<T> Deref for TrackedObject<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner.item
+    }
+}
+
+impl<T> AsRef<T> for TrackedObject<T> {
+    fn as_ref(&self) -> &T {
+        &self.inner.item
+    }
+}
+
+impl<T> Borrow<T> for TrackedObject<T> {
+    fn borrow(&self) -> &T {
+        &self.inner.item
+    }
+}
+
+/// Tracked objects are compared by identity: clones of a handle are equal,
+/// while distinct objects are never equal, even if their values are.
+///
+/// As a result, a map keyed by tracked objects cannot be looked up with
+/// a `&T`, even though `TrackedObject<T>` implements `Borrow<T>`.
+///
+/// ```rust
+/// use census::Inventory;
+/// use std::collections::HashSet;
+///
+/// let inventory = Inventory::new();
+/// let one = inventory.track(1);
+/// let other_one = inventory.track(1);
+/// let set: HashSet<_> = vec![one.clone(), one, other_one].into_iter().collect();
+/// assert_eq!(set.len(), 2);
+/// ```
+impl<T> PartialEq for TrackedObject<T> {
+    fn eq(&self, other: &TrackedObject<T>) -> bool {
+        self.ptr_eq(other)
+    }
+}
+
+impl<T> Eq for TrackedObject<T> {}
+
+/// Tracked objects are hashed by identity, that is by the address of their
+/// allocation, which never changes. Any interior mutability of `T` has no
+/// effect on the hash: `clippy::mutable_key_type` warnings about sets or maps
+/// keyed by tracked objects are false positives, and can be allowed.
+impl<T> Hash for TrackedObject<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Handle::as_ptr(&self.inner).hash(state);
+    }
+}
+
+/// A snapshot of the tracked objects, created by `Inventory::snapshot`.
+///
+/// As for `Inventory::list`, the snapshot extends the life of its objects.
+pub struct Snapshot<T>(Vec<TrackedObject<T>>);
+
+impl<T> Snapshot<T> {
+    /// Returns the number of objects in the snapshot.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if the snapshot contains no object.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over the objects of the snapshot.
+    pub fn iter(&self) -> core::slice::Iter<'_, TrackedObject<T>> {
+        self.0.iter()
+    }
+}
+
+impl<T> IntoIterator for Snapshot<T> {
+    type Item = TrackedObject<T>;
+    type IntoIter = SnapshotIter<T>;
+
+    fn into_iter(self) -> SnapshotIter<T> {
+        SnapshotIter(self.0.into_iter())
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Snapshot<T> {
+    type Item = &'a TrackedObject<T>;
+    type IntoIter = core::slice::Iter<'a, TrackedObject<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the objects of a `Snapshot`, returned by
+/// `Inventory::iter`.
+///
+/// Each object is released as soon as the caller drops it.
+pub struct SnapshotIter<T>(alloc::vec::IntoIter<TrackedObject<T>>);
+
+impl<T> Iterator for SnapshotIter<T> {
+    type Item = TrackedObject<T>;
+
+    fn next(&mut self) -> Option<TrackedObject<T>> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for SnapshotIter<T> {
+    fn next_back(&mut self) -> Option<TrackedObject<T>> {
+        self.0.next_back()
+    }
+}
+
+impl<T> ExactSizeIterator for SnapshotIter<T> {}
+
+/// An immutable snapshot of an inventory, created by `Inventory::freeze`.
+///
+/// Cloning a frozen inventory is cheap: the clones share the same objects.
+pub struct FrozenInventory<T> {
+    objects: Arc<[TrackedObject<T>]>,
+}
+
+impl<T> Clone for FrozenInventory<T> {
+    fn clone(&self) -> Self {
+        FrozenInventory {
+            objects: self.objects.clone(),
+        }
+    }
+}
+
+impl<T> FrozenInventory<T> {
+    /// Returns the number of objects in the frozen view.
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Returns true if the frozen view has no objects.
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Returns the object at position `index`.
+    pub fn get(&self, index: usize) -> Option<&TrackedObject<T>> {
+        self.objects.get(index)
+    }
+
+    /// Returns an iterator over the objects of the frozen view.
+    pub fn iter(&self) -> core::slice::Iter<'_, TrackedObject<T>> {
+        self.objects.iter()
+    }
+
+    /// Returns the first object matching `predicate`, if any.
+    pub fn find<F: Fn(&T) -> bool>(&self, predicate: F) -> Option<&TrackedObject<T>> {
+        self.objects.iter().find(|obj| predicate(obj))
+    }
+}
+
+impl<'a, T> IntoIterator for &'a FrozenInventory<T> {
+    type Item = &'a TrackedObject<T>;
+    type IntoIter = core::slice::Iter<'a, TrackedObject<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A lightweight inventory that only counts its living handles.
+///
+/// `Inventory<()>` is often used just to count handles, but it
+/// still allocates each object and keeps a weak reference to it.
+/// `CounterInventory` maintains the count alone: its handles,
+/// the `CountGuard`s, decrement it when dropped.
+///
+/// ```rust
+/// use census::CounterInventory;
+///
+/// let counter = CounterInventory::new();
+/// let guard = counter.track();
+/// assert_eq!(counter.len(), 1);
+/// drop(guard);
+/// assert_eq!(counter.len(), 0);
+/// ```
+#[derive(Clone, Default)]
+pub struct CounterInventory {
+    count: Arc<AtomicUsize>,
+}
+
+impl CounterInventory {
+    /// Creates a new empty counter.
+    pub fn new() -> CounterInventory {
+        CounterInventory::default()
+    }
+
+    /// Counts a new handle, which is uncounted when the returned guard is dropped.
+    pub fn track(&self) -> CountGuard {
+        self.count.fetch_add(1, Ordering::AcqRel);
+        CountGuard {
+            count: self.count.clone(),
+        }
+    }
+
+    /// Returns the number of living handles.
+    pub fn len(&self) -> usize {
+        self.count.load(Ordering::Acquire)
+    }
+
+    /// Returns true if there are no living handles.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A handle counted by a `CounterInventory`.
+pub struct CountGuard {
+    count: Arc<AtomicUsize>,
+}
+
+impl Drop for CountGuard {
+    fn drop(&mut self) {
+        self.count.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    //! Model checks of the locking of the inventory.
+    //!
+    //! Only the locks and the atomics of the inventory are modeled: `Arc`
+    //! and `Weak` come from `std`, as `loom` has no `Weak`. In particular,
+    //! the race between a `Weak::upgrade` in `list` and the drop of the last
+    //! handle to an object is not explored by these tests.
+
+    use super::Inventory;
+    use loom::thread;
+
+    #[test]
+    fn test_loom_birth_death_race() {
+        loom::model(|| {
+            let census = Inventory::new();
+            let census_clone = census.clone();
+            let handle = thread::spawn(move || {
+                let a = census_clone.track(1);
+                drop(a);
+                census_clone.len()
+            });
+            let b = census.track(2);
+            let len_seen_by_thread = handle.join().unwrap();
+            assert!(len_seen_by_thread <= 1);
+            assert_eq!(census.len(), 1);
+            drop(b);
+            assert_eq!(census.len(), 0);
+            assert_eq!(census.stats().total_tracked, 2);
+        });
+    }
+
+    #[test]
+    fn test_loom_snapshot_drop_race() {
+        loom::model(|| {
+            let census = Inventory::new();
+            let a = census.track(1);
+            let handle = thread::spawn(move || drop(a));
+            let snapshot = census.list();
+            assert!(snapshot.len() <= 1);
+            handle.join().unwrap();
+            assert_eq!(census.len(), snapshot.len());
+            drop(snapshot);
+            assert_eq!(census.len(), 0);
+            assert!(census.list().is_empty());
+        });
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+
+    use super::slab::Handle;
+    use super::{
+        AccessHint, CensusEvent, ClosedError, CounterInventory, GrowthPolicy, Inventory, ListOrder,
+        TrackedObject, WaitOutcome, WaitStrategy,
+    };
+    use std::collections::HashMap;
+    use std::ops::ControlFlow;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, Barrier, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_census_map() {
+        let census = Inventory::new();
+        let a = census.track(1);
+        let _b = a.map(|v| v * 7);
+        assert_eq!(census.len(), 2);
+        let mut values: Vec<_> = census.list().into_iter().map(|m| *m).collect();
+        values.sort();
+        assert_eq!(values, vec![1, 7]);
+    }
+
+    #[test]
+    fn test_census() {
+        let census = Inventory::new();
+        let _a = census.track(1);
+        let _b = census.track(3);
+        assert_eq!(census.len(), 2,);
+        let mut values: Vec<_> = census.list().into_iter().map(|m| *m).collect();
+        values.sort();
+        assert_eq!(values, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_census_2() {
+        let census = Inventory::new();
+        {
+            let _a = census.track(1);
+            let _b = census.track(3);
+            // dropping both here
+        }
+        assert_eq!(census.len(), 0);
+        assert!(census.list().is_empty());
+    }
+
+    #[test]
+    fn test_census_3() {
+        let census = Inventory::new();
+        let a = census.track(1);
+        let _a2 = a.clone();
+        drop(a);
+        assert_eq!(census.len(), 1);
+        assert_eq!(
+            census.list().into_iter().map(|m| *m).collect::<Vec<_>>(),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn test_census_list_extends_life() {
+        let census = Inventory::new();
+        let a = census.track(1);
+        let living = census.list();
+        assert_eq!(living.len(), 1);
+        drop(a);
+        let living_2 = census.list();
+        assert_eq!(living_2.len(), 1);
+        drop(living_2);
+        drop(living);
+        assert_eq!(census.len(), 0);
+        assert!(census.list().is_empty());
+    }
+
+    #[test]
+    fn test_census_race_condition() {
+        let census = Inventory::new();
+        let census_clone = census.clone();
+        thread::spawn(move || {
+            for _ in 0..1_000 {
+                let _a = census_clone.track(1);
+            }
+        });
+        for i in 0..10_000 {
+            println!("i {}", i);
+            census.list();
+        }
+    }
+
+    #[test]
+    fn test_census_concurrent_drop() {
+        let census = Inventory::new();
+        let mut senders = Vec::new();
+        let mut handles = Vec::new();
+        let barrier = Arc::new(Barrier::new(2));
+        for _ in 0..2 {
+            let (send, recv) = channel();
+            let barrier = barrier.clone();
+            handles.push(thread::spawn(move || {
+                for obj in recv {
+                    barrier.wait();
+                    drop(obj);
+                }
+            }));
+            senders.push(send);
+        }
+        for i in 0..50_000 {
+            let tracked = census.track(i);
+            for send in &senders {
+                send.send(tracked.clone()).unwrap();
+            }
+        }
+        drop(senders);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(census.len(), 0);
+    }
+
+    #[test]
+    fn test_census_stats() {
+        let census = Inventory::new();
+        let mut kept = Vec::new();
+        for i in 0..100 {
+            let tracked = census.track(i);
+            if i % 3 == 0 {
+                kept.push(tracked);
+            }
+        }
+        let stats = census.stats();
+        assert_eq!(stats.alive, kept.len());
+        assert_eq!(stats.total_tracked, 100);
+        assert!(stats.alive <= stats.peak);
+        assert!(stats.peak as u64 <= stats.total_tracked);
+        assert_eq!(stats.version, 2 * stats.total_tracked - stats.alive as u64);
+        assert!(stats.gc_passes > 0);
+        drop(kept);
+        let stats = census.stats();
+        assert_eq!(stats.alive, 0);
+        assert_eq!(stats.version, 200);
+    }
+
+    #[test]
+    fn test_census_move_to() {
+        let source = Inventory::new();
+        let dest = Inventory::new();
+        let a = source.track("a".to_string());
+        let _b = source.track("b".to_string());
+        let a = a.move_to(&dest);
+        assert_eq!(*a, "a");
+        assert_eq!(source.len(), 1);
+        assert_eq!(dest.len(), 1);
+        drop(a);
+        assert_eq!(dest.len(), 0);
+    }
+
+    #[test]
+    fn test_census_move_to_shared() {
+        let source = Inventory::new();
+        let dest = Inventory::new();
+        let a = source.track(1);
+        let a_clone = a.clone();
+        let moved = a.move_to(&dest);
+        assert_eq!(*moved, 1);
+        assert_eq!(source.len(), 1);
+        assert_eq!(dest.len(), 1);
+        drop(a_clone);
+        assert_eq!(source.len(), 0);
+        assert_eq!(dest.len(), 1);
+    }
+
+    #[test]
+    fn test_census_try_move_to() {
+        struct NotClone(u32);
+        let source = Inventory::new();
+        let dest = Inventory::new();
+        let a = source.track(NotClone(1));
+        let snapshot = source.list();
+        let a = a.try_move_to(&dest).err().unwrap();
+        assert_eq!((source.len(), dest.len()), (1, 0));
+        drop(snapshot);
+        let a = a.try_move_to(&dest).ok().unwrap();
+        assert_eq!(a.0, 1);
+        assert_eq!((source.len(), dest.len()), (0, 1));
+    }
+
+    #[test]
+    fn test_census_health() {
+        let census = Inventory::new();
+        let a = census.track(1);
+        let _b = census.track(2);
+        drop(a);
+        let health = census.health();
+        assert_eq!(health.count, 1);
+        assert!(!health.closed);
+        assert!(!health.poisoned);
+        assert!(health.is_healthy());
+    }
+
+    #[test]
+    fn test_census_health_closed() {
+        let census = Inventory::new();
+        let _a = census.track(1);
+        census.close();
+        let health = census.health();
+        assert_eq!(health.count, 1);
+        assert!(health.closed);
+        assert!(!health.is_healthy());
+    }
+
+    #[test]
+    #[should_panic(expected = "closed inventory")]
+    fn test_census_track_closed() {
+        let census = Inventory::new();
+        census.close();
+        census.track(1);
+    }
+
+    #[test]
+    fn test_census_health_poisoned() {
+        let census = Inventory::new();
+        let a = census.track(1);
+        let census_clone = census.clone();
+        let _ = thread::spawn(move || {
+            let _lock = census_clone.inner.items.write().unwrap();
+            panic!("poisoning the inventory");
+        })
+        .join();
+        let health = census.health();
+        assert_eq!(health.count, 1);
+        assert!(!health.closed);
+        assert!(health.poisoned);
+        assert!(!health.is_healthy());
+        // The inventory keeps working after the panic.
+        let b = census.track(2);
+        assert_eq!(census.list().len(), 2);
+        drop(a);
+        drop(b);
+        assert!(census.is_empty());
+        census.wait_until_empty();
+    }
+
+    #[test]
+    fn test_census_values_iter() {
+        let census = Inventory::new();
+        let mut objs: Vec<_> = (1..=10).map(|i| census.track(i)).collect();
+        let mut values = census.values_iter();
+        let first = values.next().unwrap();
+        // The iterator released the object it yielded, but not the others.
+        objs.retain(|obj| **obj != first);
+        assert_eq!(census.len(), 9);
+        drop(objs);
+        assert_eq!(census.len(), 9);
+        assert_eq!(first + values.sum::<i32>(), 55);
+        assert_eq!(census.len(), 0);
+        assert!(census.list().is_empty());
+    }
+
+    #[test]
+    fn test_census_assert_no_growth() {
+        let census = Inventory::new();
+        let _a = census.track(1);
+        let res = census.assert_no_growth(|| {
+            let _tmp = census.track(2);
+            3
+        });
+        assert_eq!(res, 3);
+        let _leaked = census.assert_growth_at_most(1, || census.track(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "Leaked objects: [Tracked(2)]")]
+    fn test_census_assert_no_growth_leak() {
+        let census = Inventory::new();
+        let _a = census.track(1);
+        let mut leaked = Vec::new();
+        census.assert_no_growth(|| leaked.push(census.track(2)));
+    }
+
+    #[test]
+    fn test_census_assert_no_growth_leak_reusing_slot() {
+        let census = Inventory::builder().slab(true).build();
+        let mut dying = Some(census.track(1));
+        let mut leaked = Vec::new();
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            census.assert_no_growth(|| {
+                let dying_ptr = Handle::as_ptr(&dying.as_ref().unwrap().inner);
+                drop(dying.take());
+                leaked.push(census.track(2));
+                // The leaked object takes the slot of the dead one.
+                assert_eq!(Handle::as_ptr(&leaked[0].inner), dying_ptr);
+                leaked.push(census.track(3));
+            })
+        }));
+        let msg = *res.unwrap_err().downcast::<String>().unwrap();
+        assert!(msg.contains("Tracked(2)"), "{}", msg);
+        assert!(msg.contains("Tracked(3)"), "{}", msg);
+    }
+
+    #[test]
+    fn test_census_track_with_id() {
+        let census = Inventory::new();
+        let (a, id_a) = census.track_with_id(1);
+        let (b, id_b) = census.track_with_id(1);
+        assert_eq!(a.id(), id_a);
+        assert_eq!(b.id(), id_b);
+        assert_ne!(id_a, id_b);
+    }
+
+    #[test]
+    fn test_tracked_object_ids() {
+        let census = Inventory::new();
+        let a = census.track(1);
+        let a_clone = a.clone();
+        assert_eq!(a.id(), a_clone.id());
+        let mapped = a.map(|i| i * 2);
+        assert_ne!(mapped.id(), a.id());
+        let b = census.track(1);
+        assert_ne!(b.id(), a.id());
+        assert_ne!(b.id(), mapped.id());
+        // Ids of dead objects are not reused.
+        let b_id = b.id();
+        drop(b);
+        let c = census.track(1);
+        assert!(c.id() > b_id);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_census_par_for_each() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let census = Inventory::new();
+        let _objs: Vec<_> = (0..10_000usize).map(|i| census.track(i)).collect();
+        let sum = AtomicUsize::new(0);
+        census.par_for_each(|&i| {
+            sum.fetch_add(i, Ordering::Relaxed);
+        });
+        let serial_sum: usize = census.list().into_iter().map(|i| *i).sum();
+        assert_eq!(sum.load(Ordering::Relaxed), serial_sum);
+    }
+
+    #[test]
+    fn test_census_key_quota() {
+        let census = Inventory::with_key_quota(1, |v: &(char, u32)| v.0);
+        let a1 = census.try_track(('a', 1)).unwrap();
+        let _b1 = census.try_track(('b', 1)).unwrap();
+        assert_eq!(census.try_track(('a', 2)).unwrap_err(), ('a', 2));
+        assert_eq!(census.try_track(('b', 2)).unwrap_err(), ('b', 2));
+        assert_eq!(census.len(), 2);
+        drop(a1);
+        let _a3 = census.try_track(('a', 3)).unwrap();
+        assert_eq!(census.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "quota is exceeded")]
+    fn test_census_key_quota_track_panics() {
+        let census = Inventory::with_key_quota(1, |v: &u32| *v);
+        let _a = census.track(1);
+        census.track(1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_census_to_report() {
+        let census = Inventory::with_name("sessions");
+        let a = census.track("a".to_string());
+        let _b = a.map(|v| format!("{}b", v));
+        drop(a);
+        let report = census.to_report();
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["name"], "sessions");
+        assert_eq!(json["alive"], 1);
+        assert_eq!(json["peak"], 2);
+        assert_eq!(json["total_tracked"], 2);
+        assert_eq!(json["objects"].as_array().unwrap().len(), 1);
+        assert_eq!(json["objects"][0]["id"], 1);
+        assert_eq!(json["objects"][0]["value"], "ab");
+        assert!(json["objects"][0]["age_ms"].is_u64());
+    }
+
+    #[test]
+    fn test_census_count_drops_in() {
+        let census = Inventory::new();
+        let mut objs: Vec<_> = (0..5).map(|i| census.track(i)).collect();
+        let (len, dropped) = census.count_drops_in(|| {
+            objs.truncate(2);
+            let _tmp = census.track(5);
+            census.len()
+        });
+        assert_eq!(len, 3);
+        assert_eq!(dropped, 4);
+        let ((), dropped) = census.count_drops_in(|| {});
+        assert_eq!(dropped, 0);
+    }
+
+    #[test]
+    fn test_census_panicking_drop() {
+        struct PanicOnDrop;
+
+        impl Drop for PanicOnDrop {
+            fn drop(&mut self) {
+                panic!("panicking in drop");
+            }
+        }
+
+        let census = Inventory::new();
+        let a = census.track(PanicOnDrop);
+        let b = census.track(PanicOnDrop);
+        let res = panic::catch_unwind(AssertUnwindSafe(|| drop(a)));
+        assert!(res.is_err());
+        assert_eq!(census.len(), 1);
+        assert_eq!(census.health().count, 1);
+        census.wait_until_predicate(|count| count == 1);
+        std::mem::forget(b);
+    }
+
+    #[test]
+    fn test_census_with_max() {
+        let census = Inventory::with_max(2);
+        let a = census.try_track(1).unwrap();
+        let _b = census.try_track(2).unwrap();
+        assert_eq!(census.try_track(3).unwrap_err(), 3);
+        drop(a);
+        assert!(census.try_track(3).is_ok());
+    }
+
+    #[test]
+    fn test_census_with_max_concurrent_try_track() {
+        const MAX: usize = 50;
+        let census = Inventory::with_max(MAX);
+        let barrier = Arc::new(Barrier::new(8));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let census = census.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    let mut held = Vec::new();
+                    for i in 0..1_000 {
+                        match census.try_track(i) {
+                            Ok(obj) => held.push(obj),
+                            Err(item) => assert_eq!(item, i),
+                        }
+                        assert!(census.len() <= MAX);
+                        if held.len() > 10 {
+                            held.clear();
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(census.is_empty());
+        // The inventory without a maximum is unbounded.
+        let unbounded = Inventory::new();
+        let objs: Vec<_> = (0..MAX * 2)
+            .map(|i| unbounded.try_track(i).unwrap())
+            .collect();
+        assert_eq!(unbounded.len(), objs.len());
+    }
+
+    #[test]
+    fn test_census_capacity_alarm() {
+        let census = Inventory::with_max(10);
+        let (send, recv) = channel();
+        let send = Mutex::new(send);
+        census.set_capacity_alarm(0.9, move |alive, max| {
+            send.lock().unwrap().send((alive, max)).unwrap();
+        });
+        let mut objs: Vec<_> = (0..8).map(|i| census.track(i)).collect();
+        assert!(recv.try_recv().is_err());
+        objs.push(census.track(8));
+        objs.push(census.track(9));
+        assert_eq!(recv.try_iter().collect::<Vec<_>>(), vec![(9, 10)]);
+        objs.truncate(5);
+        objs.push(census.track(5));
+        assert!(recv.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_census_compact() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..100).map(|i| census.track(i)).collect();
+        let kept: Vec<_> = objs.iter().take(60).cloned().collect();
+        drop(objs);
+        // 60 alive out of 100 slots do not trigger the lazy collection.
+        assert_eq!(census.len(), 60);
+        assert_eq!(census.compact(), (100, 60));
+        assert_eq!(census.compact(), (60, 60));
+        drop(kept);
+        assert_eq!(census.compact().1, 0);
+    }
+
+    #[test]
+    fn test_census_track_into() {
+        let census: Inventory<String> = Inventory::new();
+        let a = census.track_into("a");
+        assert_eq!(*a, "a");
+        assert_eq!(
+            census.list().iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+            vec!["a"]
+        );
+    }
+
+    #[test]
+    fn test_census_wait_ex_satisfied() {
+        let census = Inventory::new();
+        let a = census.track(1);
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            drop(a);
+        });
+        let outcome =
+            census.wait_until_predicate_ex(|count| count == 0, Some(Duration::from_secs(10)), None);
+        assert_eq!(outcome, WaitOutcome::Satisfied);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_census_wait_ex_timed_out() {
+        let census = Inventory::new();
+        let _a = census.track(1);
+        let start = Instant::now();
+        let outcome = census.wait_until_predicate_ex(
+            |count| count == 0,
+            Some(Duration::from_millis(20)),
+            None,
+        );
+        assert_eq!(outcome, WaitOutcome::TimedOut);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_census_wait_ex_cancelled() {
+        let census = Inventory::new();
+        let _a = census.track(1);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let cancel_clone = cancel.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            cancel_clone.store(true, Ordering::SeqCst);
+        });
+        let outcome = census.wait_until_predicate_ex(|count| count == 0, None, Some(&cancel));
+        assert_eq!(outcome, WaitOutcome::Cancelled);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_census_list_capped() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..100).map(|i| census.track(i)).collect();
+        assert_eq!(census.list_capped(3).len(), 3);
+        assert_eq!(census.list_capped(0).len(), 0);
+        drop(objs);
+        let _a = census.track(1);
+        assert_eq!(census.list_capped(3).len(), 1);
+    }
+
+    #[test]
+    fn test_census_scoped() {
+        let census = Inventory::new();
+        let doubled = census.scoped(21, |obj| {
+            assert_eq!(census.len(), 1);
+            assert_eq!(*census.list()[0], 21);
+            **obj * 2
+        });
+        assert_eq!(doubled, 42);
+        assert_eq!(census.len(), 0);
+        assert!(census.list().is_empty());
+    }
+
+    #[test]
+    fn test_census_wait_until_empty_with() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..3).map(|i| census.track(i)).collect();
+        let leaked = census.track(3);
+        let handle = thread::spawn(move || {
+            for obj in objs {
+                thread::sleep(Duration::from_millis(5));
+                drop(obj);
+            }
+        });
+        let mut counts = Vec::new();
+        let emptied = census.wait_until_empty_with(|count, elapsed| {
+            counts.push(count);
+            if count == 1 && elapsed >= Duration::from_millis(50) {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert!(!emptied);
+        assert_eq!(counts.first(), Some(&4));
+        assert_eq!(counts.last(), Some(&1));
+        handle.join().unwrap();
+        drop(leaked);
+        assert!(census.wait_until_empty_with(|_, _| ControlFlow::Break(())));
+    }
+
+    #[test]
+    fn test_census_sample() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..100).map(|i| census.track(i)).collect();
+        let mut sample: Vec<usize> = census.sample(10).into_iter().map(|v| *v).collect();
+        sample.sort();
+        sample.dedup();
+        assert_eq!(sample.len(), 10);
+        assert!(sample.iter().all(|&v| v < 100));
+        assert_eq!(census.sample(1000).len(), 100);
+        assert!(census.sample(0).is_empty());
+        drop(objs);
+        assert!(census.sample(10).is_empty());
+    }
+
+    #[test]
+    fn test_census_profile_sample() {
+        let census = Inventory::new();
+        let old: Vec<_> = (0..5).map(|i| census.track(i)).collect();
+        thread::sleep(Duration::from_millis(20));
+        let _young = census.track(5);
+        let sample = census.profile_sample(3);
+        assert_eq!(sample.len(), 3);
+        for (id, age, value) in sample {
+            assert_eq!(id, value as u64);
+            assert!(age < Duration::from_secs(60));
+            if value < 5 {
+                assert!(age >= Duration::from_millis(20));
+            }
+        }
+        drop(old);
+        assert_eq!(census.profile_sample(3).len(), 1);
+    }
+
+    #[test]
+    fn test_census_close_wakes_up_waiters() {
+        let census = Inventory::new();
+        let _a = census.track(1);
+        let barrier = Arc::new(Barrier::new(4));
+        let handles: Vec<_> = (0..3)
+            .map(|i| {
+                let census = census.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    census.wait_until_predicate_ex(move |count| count == i + 10, None, None)
+                })
+            })
+            .collect();
+        barrier.wait();
+        thread::sleep(Duration::from_millis(20));
+        census.close();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), WaitOutcome::Closed);
+        }
+        census.wait_until_empty();
+    }
+
+    #[test]
+    fn test_census_write_dump() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..3000).map(|i| census.track(i)).collect();
+        let mut dump = Vec::new();
+        census.write_dump(&mut dump).unwrap();
+        let dump = String::from_utf8(dump).unwrap();
+        assert_eq!(dump.lines().count(), 3000);
+        assert!(dump.lines().any(|line| line == "2 2"));
+        assert!(dump.lines().all(|line| {
+            let mut fields = line.split(' ');
+            fields.next() == fields.next()
+        }));
+        drop(objs);
+        let mut dump = Vec::new();
+        census.write_dump(&mut dump).unwrap();
+        assert!(dump.is_empty());
+    }
+
+    #[test]
+    fn test_census_lineage() {
+        let census = Inventory::new();
+        let a = census.track(1);
+        let b = a.map(|v| v + 1);
+        let c = b.map(|v| v + 1);
+        let d = census.track(4);
+        let expected: HashMap<u64, Option<u64>> = vec![
+            (a.id(), None),
+            (b.id(), Some(a.id())),
+            (c.id(), Some(b.id())),
+            (d.id(), None),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(census.lineage(), expected);
+        drop(b);
+        assert_eq!(census.lineage().len(), 3);
+        assert_eq!(census.lineage()[&c.id()], Some(a.id() + 1));
+    }
+
+    #[test]
+    fn test_census_visit_concurrent_drop() {
+        let census = Inventory::new();
+        let (send, recv) = channel();
+        let handle = thread::spawn(move || {
+            for obj in recv {
+                drop(obj);
+            }
+        });
+        for i in 0..10_000 {
+            send.send(census.track(i)).unwrap();
+            census.lineage();
+        }
+        drop(send);
+        handle.join().unwrap();
+        assert_eq!(census.len(), 0);
+    }
+
+    #[test]
+    fn test_census_park_wait_strategy() {
+        let census = Inventory::builder()
+            .wait_strategy(WaitStrategy::Park)
+            .build();
+        let a = census.track(1);
+        let census_clone = census.clone();
+        let waiter = thread::spawn(move || census_clone.wait_until_empty());
+        thread::sleep(Duration::from_millis(20));
+        let dropper = thread::spawn(move || drop(a));
+        dropper.join().unwrap();
+        waiter.join().unwrap();
+        assert_eq!(census.len(), 0);
+        let _b = census.track(2);
+        let outcome = census.wait_until_predicate_ex(
+            |count| count == 0,
+            Some(Duration::from_millis(10)),
+            None,
+        );
+        assert_eq!(outcome, WaitOutcome::TimedOut);
+    }
+
+    #[test]
+    fn test_census_park_wait_strategy_many() {
+        let census = Inventory::builder()
+            .wait_strategy(WaitStrategy::Park)
+            .build();
+        for i in 0..100 {
+            let tracked = census.track(i);
+            thread::spawn(move || {
+                let _tracked = tracked;
+            });
+        }
+        census.wait_until_empty();
+        assert_eq!(census.len(), 0);
+    }
+
+    #[test]
+    fn test_census_track_batch() {
+        let census = Inventory::new();
+        let mut batch = census.track_batch(0..5);
+        assert_eq!(census.len(), 5);
+        assert_eq!(batch.len(), 5);
+        assert_eq!(batch.get(3).map(|obj| **obj), Some(3));
+        assert!(batch.release(1));
+        assert!(batch.release(3));
+        assert!(!batch.release(3));
+        assert!(!batch.release(10));
+        assert!(batch.get(3).is_none());
+        assert_eq!(batch.len(), 3);
+        assert_eq!(census.len(), 3);
+        let kept = batch.get(4).unwrap().clone();
+        drop(batch);
+        assert_eq!(census.len(), 1);
+        drop(kept);
+        assert_eq!(census.len(), 0);
+    }
+
+    #[test]
+    fn test_census_collect_values() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..10).map(|i| census.track(i)).collect();
+        let mut evens = census.collect_values(|&i| {
+            if i % 2 == 0 {
+                Some(format!("#{}", i))
+            } else {
+                None
+            }
+        });
+        evens.sort();
+        assert_eq!(evens, vec!["#0", "#2", "#4", "#6", "#8"]);
+        drop(objs);
+        assert_eq!(census.len(), 0);
+        assert!(census.collect_values(|&i| Some(i)).is_empty());
+    }
+
+    #[test]
+    fn test_census_watchdog() {
+        let census = Inventory::new();
+        let leaked = census.track(1);
+        let _other = census.track(2);
+        drop(leaked);
+        let (send, recv) = channel();
+        let watchdog = census.spawn_watchdog(Duration::from_millis(20), move |count| {
+            send.send(count).unwrap();
+        });
+        watchdog.join().unwrap();
+        assert_eq!(recv.try_iter().collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_census_watchdog_empty() {
+        let census = Inventory::new();
+        let a = census.track(1);
+        let (send, recv) = channel();
+        let watchdog = census.spawn_watchdog(Duration::from_secs(60), move |count| {
+            send.send(count).unwrap();
+        });
+        drop(a);
+        watchdog.join().unwrap();
+        assert!(recv.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_census_reclaim() {
+        let census = Inventory::new();
+        let other_census = Inventory::new();
+        let mut objs: Vec<_> = (0..5).map(|i| census.track(i.to_string())).collect();
+        objs.push(other_census.track("other".to_string()));
+        let shared = objs[2].clone();
+        let mut reclaimed = census.reclaim(objs);
+        reclaimed.sort();
+        assert_eq!(reclaimed, vec!["0", "1", "3", "4", "other"]);
+        assert_eq!(census.len(), 1);
+        assert_eq!(other_census.len(), 0);
+        assert_eq!(*census.list()[0], "2");
+        drop(shared);
+        assert_eq!(census.len(), 0);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_census_count_watch() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let census = Inventory::new();
+        let mut count = census.count_watch();
+        assert_eq!(*count.borrow_and_update(), 0);
+        let census_clone = census.clone();
+        let handle = thread::spawn(move || {
+            let a = census_clone.track(1);
+            let b = census_clone.track(2);
+            thread::sleep(Duration::from_millis(50));
+            drop(a);
+            drop(b);
+        });
+        let mut max_count = 0;
+        loop {
+            runtime.block_on(count.changed()).unwrap();
+            let current = *count.borrow_and_update();
+            max_count = max_count.max(current);
+            if current == 0 {
+                break;
+            }
+        }
+        assert_eq!(max_count, 2);
+        handle.join().unwrap();
+    }
+
+    #[cfg(feature = "backtrace")]
+    #[test]
+    fn test_census_tracked_at() {
+        let census = Inventory::new();
+        let (one, line) = (census.track(1), line!());
+        assert_eq!(one.tracked_at().line(), line);
+        assert_eq!(one.tracked_at().file(), file!());
+        let (two, line) = (one.map(|i| i + 1), line!());
+        assert_eq!(two.tracked_at().line(), line);
+    }
+
+    #[test]
+    fn test_census_list_cached() {
+        let census = Inventory::new();
+        let _one = census.track(1);
+        let first = census.list_cached();
+        let second = census.list_cached();
+        assert!(Arc::ptr_eq(&first, &second));
+        let _two = census.track(2);
+        let third = census.list_cached();
+        assert!(!Arc::ptr_eq(&first, &third));
+        assert_eq!(third.len(), 2);
+        assert_eq!(first.len(), 1);
+        // The cache does not pin the objects.
+        drop((first, second, third, _two));
+        assert_eq!(census.len(), 1);
+    }
+
+    #[test]
+    fn test_census_sweep_expired() {
+        let census = Inventory::new();
+        let _old = census.track(1);
+        thread::sleep(Duration::from_millis(50));
+        let _young = census.track(2);
+        let mut expired = Vec::new();
+        let num_expired = census.sweep_expired(Duration::from_millis(50), |&i| expired.push(i));
+        assert_eq!(num_expired, 1);
+        assert_eq!(expired, vec![1]);
+        assert_eq!(census.len(), 2);
+    }
+
+    #[test]
+    fn test_counter_inventory() {
+        let counter = CounterInventory::new();
+        assert!(counter.is_empty());
+        let guards: Vec<_> = (0..10).map(|_| counter.track()).collect();
+        let other = counter.clone();
+        let handle = thread::spawn(move || {
+            let _guard = other.track();
+            other.len()
+        });
+        assert_eq!(handle.join().unwrap(), 11);
+        assert_eq!(counter.len(), 10);
+        drop(guards);
+        assert!(counter.is_empty());
+    }
+
+    fn list_values_in_order(list_order: ListOrder) -> Vec<usize> {
+        let census = Inventory::builder().list_order(list_order).build();
+        let mut objs: Vec<_> = (0..10).map(|i| census.track(i)).collect();
+        // Dropping most of the objects triggers garbage collection.
+        objs.retain(|obj| **obj % 3 == 0);
+        let _ten = census.track(10);
+        census.list().iter().map(|obj| **obj).collect()
+    }
+
+    #[test]
+    fn test_census_list_order() {
+        assert_eq!(
+            list_values_in_order(ListOrder::InsertionAsc),
+            vec![0, 3, 6, 9, 10]
+        );
+        assert_eq!(
+            list_values_in_order(ListOrder::InsertionDesc),
+            vec![10, 9, 6, 3, 0]
+        );
+        let mut unspecified = list_values_in_order(ListOrder::Unspecified);
+        unspecified.sort();
+        assert_eq!(unspecified, vec![0, 3, 6, 9, 10]);
+    }
+
+    #[test]
+    fn test_census_checked_track() {
+        let census = Inventory::new();
+        let one = census.checked_track(1).unwrap();
+        assert_eq!(*one, 1);
+        census.close();
+        let err: ClosedError<i32> = census.checked_track(2).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot track an object in a closed inventory"
+        );
+        assert_eq!(err.into_inner(), 2);
+        assert_eq!(census.len(), 1);
+    }
+
+    #[test]
+    fn test_census_age_histogram() {
+        let census = Inventory::new();
+        let _old = census.track(1);
+        thread::sleep(Duration::from_millis(100));
+        let _young = census.track(2);
+        let _younger = census.track(3);
+        let buckets = [Duration::from_millis(50), Duration::from_secs(3600)];
+        assert_eq!(census.age_histogram(&buckets), vec![2, 1, 0]);
+        assert_eq!(census.age_histogram(&[]), vec![3]);
+    }
+
+    #[test]
+    fn test_census_try_for_each() {
+        let census = Inventory::new();
+        let _objs: Vec<_> = (0..10).map(|i| census.track(i)).collect();
+        let mut visited = 0;
+        let found = census.try_for_each(|&i| {
+            visited += 1;
+            if i >= 3 {
+                ControlFlow::Break(i.to_string())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert!(found.is_some());
+        assert!(visited < 10);
+        let mut visited = 0;
+        let not_found: Option<()> = census.try_for_each(|_| {
+            visited += 1;
+            ControlFlow::Continue(())
+        });
+        assert_eq!(not_found, None);
+        assert_eq!(visited, 10);
+    }
+
+    #[test]
+    fn test_census_shared_objects() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..5).map(|i| census.track(i)).collect();
+        let clones: Vec<_> = (0..3).map(|_| objs[2].clone()).collect();
+        let shared = census.shared_objects(4);
+        assert_eq!(shared.len(), 1);
+        assert_eq!(*shared[0], 2);
+        drop(shared);
+        assert!(census.shared_objects(5).is_empty());
+        assert_eq!(census.shared_objects(1).len(), 5);
+        drop(clones);
+        assert!(census.shared_objects(2).is_empty());
+    }
+
+    #[test]
+    fn test_census_freeze() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..100).map(|i| census.track(i)).collect();
+        let frozen = census.freeze();
+        drop(objs);
+        let _extra = census.track(100);
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let frozen = frozen.clone();
+                thread::spawn(move || frozen.iter().map(|obj| **obj).sum::<usize>())
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 4950);
+        }
+        assert_eq!(frozen.len(), 100);
+        assert_eq!(frozen.find(|&i| i == 42).map(|obj| **obj), Some(42));
+        assert!(frozen.get(100).is_none());
+        assert_eq!(census.len(), 101);
+        drop(frozen);
+        assert_eq!(census.len(), 1);
+    }
+
+    #[test]
+    fn test_census_top_k_by_key() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..100).map(|i| census.track((i * 37) % 100)).collect();
+        let top: Vec<usize> = census
+            .top_k_by_key(3, |&i| i)
+            .iter()
+            .map(|obj| **obj)
+            .collect();
+        assert_eq!(top, vec![99, 98, 97]);
+        assert!(census.top_k_by_key(0, |&i| i).is_empty());
+        assert_eq!(census.top_k_by_key(200, |&i| i).len(), 100);
+        drop(objs);
+        assert_eq!(census.len(), 0);
+    }
+
+    #[test]
+    fn test_census_strict_churn() {
+        let census = Inventory::builder().strict(true).build();
+        let handles: Vec<_> = (0..4)
+            .map(|thread_id| {
+                let census = census.clone();
+                thread::spawn(move || {
+                    let mut objs = Vec::new();
+                    for i in 0..500 {
+                        objs.push(census.track(thread_id * 1000 + i));
+                        if i % 3 == 0 {
+                            objs.swap_remove(i % objs.len());
+                        }
+                    }
+                    let batch = census.track_batch(0..10);
+                    drop((objs, batch));
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(census.len(), 0);
+    }
+
+    #[test]
+    fn test_census_leaderboard() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..100)
+            .map(|i| census.track(if i < 50 { 0 } else { i % 4 }))
+            .collect();
+        assert_eq!(
+            census.leaderboard(|&i| i, 3),
+            vec![(0, 50 + 12), (2, 13), (3, 13)]
+        );
+        assert_eq!(census.leaderboard(|&i| i, 10).len(), 4);
+        assert!(census.leaderboard(|&i| i, 0).is_empty());
+        drop(objs);
+        assert!(census.leaderboard(|&i| i, 3).is_empty());
+    }
+
+    #[test]
+    fn test_census_growth_policy() {
+        let census = Inventory::builder()
+            .growth_policy(GrowthPolicy::Fixed(10))
+            .build();
+        let mut objs = Vec::new();
+        let mut capacities = Vec::new();
+        for i in 0..25 {
+            objs.push(census.track(i));
+            capacities.push(census.stats().capacity);
+        }
+        assert_eq!(&capacities[..10], &[10; 10]);
+        assert_eq!(&capacities[10..20], &[20; 10]);
+        assert_eq!(&capacities[20..], &[30; 5]);
+
+        let census = Inventory::builder()
+            .growth_policy(GrowthPolicy::Exponential(1.5))
+            .build();
+        let objs: Vec<_> = (0..5).map(|i| census.track(i)).collect();
+        // 0 -> 1 -> 2 -> 3 -> 5
+        assert_eq!(census.stats().capacity, 5);
+        drop(objs);
+    }
+
+    #[test]
+    fn test_census_find_up_to() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..1000).map(|i| census.track(i)).collect();
+        let found = census.find_up_to(|&i| i % 10 != 0, 7);
+        assert_eq!(found.len(), 7);
+        assert!(found.iter().all(|obj| **obj % 10 != 0));
+        assert_eq!(census.find_up_to(|&i| i % 100 == 0, 50).len(), 10);
+        assert!(census.find_up_to(|_| true, 0).is_empty());
+        drop((objs, found));
+        assert_eq!(census.len(), 0);
+    }
+
+    #[test]
+    fn test_census_total_dropped() {
+        let census = Inventory::new();
+        let mut objs: Vec<_> = (0..10).map(|i| census.track(i)).collect();
+        objs.truncate(6);
+        assert_eq!(census.total_dropped(), 4);
+        let stats = census.stats();
+        assert_eq!(stats.total_dropped, 4);
+        assert_eq!(
+            stats.alive as u64,
+            stats.total_tracked - stats.total_dropped
+        );
+        let reclaimed = census.reclaim(objs.drain(..2));
+        assert_eq!(reclaimed.len(), 2);
+        assert_eq!(census.total_dropped(), 6);
+        assert_eq!(census.len(), 4);
+    }
+
+    #[test]
+    fn test_census_wait_until_empty_reporting() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..3).map(|i| census.track(i)).collect();
+        let handle = thread::spawn(move || {
+            for obj in objs {
+                thread::sleep(Duration::from_millis(20));
+                drop(obj);
+            }
+        });
+        let mut reports = Vec::new();
+        census.wait_until_empty_reporting(Duration::from_millis(5), |count| reports.push(count));
+        handle.join().unwrap();
+        assert!(!reports.is_empty());
+        assert!(reports.iter().all(|&count| count > 0 && count <= 3));
+        assert_eq!(census.len(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "interval must not be zero")]
+    fn test_census_wait_until_empty_reporting_zero_interval() {
+        let census = Inventory::new();
+        let _one = census.track(1);
+        census.wait_until_empty_reporting(Duration::ZERO, |_| {});
+    }
+
+    #[test]
+    fn test_census_fan_out() {
+        let census = Inventory::new();
+        let source = census.track(10);
+        let version_before = census.stats().version;
+        let children = source.fan_out(|&i| vec![i + 1, i + 2, i + 3]);
+        assert_eq!(census.stats().version, version_before + 3);
+        let mut values: Vec<usize> = census.list().iter().map(|obj| **obj).collect();
+        values.sort();
+        assert_eq!(values, vec![10, 11, 12, 13]);
+        assert!(children
+            .iter()
+            .all(|child| child.parent_id() == Some(source.id())));
+    }
+
+    #[test]
+    fn test_census_display() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..3).map(|i| census.track(i)).collect();
+        assert_eq!(census.to_string(), "Inventory(alive=3)");
+        drop(objs);
+        assert_eq!(census.to_string(), "Inventory(alive=0)");
+        let named: Inventory<()> = Inventory::with_name("sockets");
+        assert_eq!(format!("{}", named), "Inventory(sockets, alive=0)");
+    }
+
+    #[test]
+    fn test_census_wait_until_snapshot() {
+        let census = Inventory::new();
+        let pending: Vec<_> = (0..4).map(|_| census.track(false)).collect();
+        let census_clone = census.clone();
+        let handle = thread::spawn(move || {
+            let mut ready = Vec::new();
+            for obj in pending {
+                thread::sleep(Duration::from_millis(10));
+                ready.push(census_clone.track(true));
+                drop(obj);
+            }
+            ready
+        });
+        let snapshot =
+            census.wait_until_snapshot(|objs| objs.len() == 4 && objs.iter().all(|obj| **obj));
+        assert_eq!(snapshot.len(), 4);
+        let ready = handle.join().unwrap();
+        drop((snapshot, ready));
+        assert_eq!(census.len(), 0);
+    }
+
+    #[test]
+    fn test_census_len_with_snapshot() {
+        let census = Inventory::new();
+        assert!(census.is_empty());
+        let objs: Vec<_> = (0..10).map(|i| census.track(i)).collect();
+        assert_eq!(census.len(), 10);
+        let snapshot = census.list();
+        drop(objs);
+        assert_eq!(census.len(), 10);
+        assert_eq!(census.len(), census.list().len());
+        assert!(!census.is_empty());
+        drop(snapshot);
+        assert_eq!(census.len(), 0);
+        assert!(census.is_empty());
+    }
+
+    #[test]
+    fn test_census_new_child() {
+        let parent = Inventory::new();
+        let child = parent.new_child();
+        let grandchild = child.new_child();
+        let a = child.track(1);
+        assert_eq!((parent.len(), child.len()), (1, 1));
+        let b = grandchild.track(2);
+        let c = parent.track(3);
+        assert_eq!((parent.len(), child.len(), grandchild.len()), (3, 2, 1));
+        let mut values: Vec<usize> = parent.list().iter().map(|obj| **obj).collect();
+        values.sort();
+        assert_eq!(values, vec![1, 2, 3]);
+        let mut ids = vec![a.id(), b.id(), c.id()];
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 3);
+        let batch = grandchild.track_batch(vec![4, 5]);
+        assert_eq!(parent.len(), 5);
+        drop(parent);
+        drop((a, b, batch));
+        assert_eq!(child.len(), 0);
+        assert_eq!(grandchild.len(), 0);
+        assert_eq!(c.inner.census.len(), 1);
+        drop(c);
+    }
+
+    #[test]
+    fn test_census_new_child_max_alive() {
+        let parent = Inventory::with_max(2);
+        let child = parent.new_child();
+        let children: Vec<_> = (0..3).map(|i| child.track(i)).collect();
+        assert_eq!(parent.len(), 3);
+        let one = parent.try_track(10).unwrap();
+        let two = parent.checked_track(11).unwrap();
+        assert_eq!(parent.try_track(12).map(|obj| *obj), Err(12));
+        assert_eq!(parent.len(), 5);
+        drop(children);
+        assert_eq!(parent.try_track(12).map(|obj| *obj), Err(12));
+        drop(one);
+        let three = parent.try_track(12).unwrap();
+        assert_eq!(parent.len(), 2);
+        drop((two, three));
+        assert!(parent.is_empty());
+    }
+
+    #[test]
+    fn test_census_collect_on_drain() {
+        let census = Inventory::new();
+        let dead_before = census.track(0);
+        drop(dead_before);
+        let mut objs: Vec<_> = (1..5).map(|i| census.track(i)).collect();
+        let values = census.collect_on_drain();
+        census.close();
+        let handle = thread::spawn(move || values.iter().collect::<Vec<_>>());
+        let last = objs.remove(0);
+        while let Some(obj) = objs.pop() {
+            drop(obj);
+        }
+        let batch_values = {
+            let batch_census = Inventory::new();
+            let batch_values = batch_census.collect_on_drain();
+            drop(batch_census.track_batch(vec![10, 11]));
+            batch_values.try_iter().collect::<Vec<_>>()
+        };
+        assert_eq!(batch_values, vec![10, 11]);
+        drop(last);
+        assert_eq!(handle.join().unwrap(), vec![4, 3, 2, 1]);
+        assert!(census.collect_on_drain().recv().is_err());
+    }
+
+    #[test]
+    fn test_census_wait_until_empty_timeout() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..3).map(|i| census.track(i)).collect();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            drop(objs);
+        });
+        assert!(census.wait_until_empty_timeout(Duration::from_secs(10)));
+        handle.join().unwrap();
+
+        let _leaked = census.track(1);
+        let start = Instant::now();
+        assert!(!census.wait_until_empty_timeout(Duration::from_millis(30)));
+        assert!(start.elapsed() >= Duration::from_millis(30));
+        assert!(census.wait_until_predicate_timeout(|count| count == 1, Duration::ZERO));
+    }
+
+    #[test]
+    fn test_tracked_object_ptr_eq() {
+        #[derive(Clone)]
+        struct NotComparable(u32);
+        let census = Inventory::new();
+        let one = census.track(NotComparable(1));
+        let one_clone = one.clone();
+        assert!(one.ptr_eq(&one_clone));
+        assert!(one_clone.ptr_eq(&one));
+        let mapped = one.map(|value| NotComparable(value.0));
+        assert!(!one.ptr_eq(&mapped));
+        let other_one = census.track(NotComparable(1));
+        assert!(!one.ptr_eq(&other_one));
+        assert_eq!(other_one.0, 1);
+    }
+
+    #[test]
+    fn test_census_last_death_seq() {
+        let census = Inventory::new();
+        let mut objs: Vec<_> = (0..5).map(|i| census.track(i)).collect();
+        assert_eq!(census.last_death_seq(), None);
+        let mut death_seqs = HashMap::new();
+        for &i in &[3, 0, 1] {
+            let pos = objs.iter().position(|obj| **obj == i).unwrap();
+            drop(objs.remove(pos));
+            death_seqs.insert(i, census.last_death_seq().unwrap());
+        }
+        assert_eq!(death_seqs[&3], 1);
+        assert_eq!(death_seqs[&0], 2);
+        assert_eq!(death_seqs[&1], 3);
+        drop(census.track_batch(vec![5, 6]));
+        assert_eq!(census.last_death_seq(), Some(5));
+        drop(objs);
+        assert_eq!(census.last_death_seq(), Some(7));
+    }
+
+    #[test]
+    fn test_census_cohorts() {
+        let census = Inventory::new();
+        let _old: Vec<_> = (0..2).map(|i| census.track(i)).collect();
+        thread::sleep(Duration::from_millis(120));
+        let _young: Vec<_> = (2..5).map(|i| census.track(i)).collect();
+        let cohorts = census.cohorts(Duration::from_millis(100));
+        assert_eq!(cohorts.len(), 2);
+        let mut young: Vec<usize> = cohorts[&0].iter().map(|obj| **obj).collect();
+        young.sort();
+        assert_eq!(young, vec![2, 3, 4]);
+        let (&old_cohort, old) = cohorts.iter().next_back().unwrap();
+        assert!(old_cohort >= 1);
+        assert_eq!(old.len(), 2);
+    }
+
+    #[test]
+    fn test_tracked_object_map_into() {
+        let numbers = Inventory::new();
+        let strings = Inventory::new();
+        let forty_two = numbers.track(42u32);
+        let forty_two_str = forty_two.map_into(&strings, |i| format!("{}!", i));
+        assert_eq!(*forty_two_str, "42!");
+        assert_eq!(numbers.list().len(), 1);
+        assert_eq!(strings.list().len(), 1);
+        assert_eq!(forty_two_str.parent_id(), None);
+        drop(forty_two);
+        assert_eq!(numbers.list().len(), 0);
+        assert_eq!(strings.list().len(), 1);
+    }
+
+    #[test]
+    fn test_census_for_each() {
+        let census = Inventory::new();
+        let mut objs: Vec<_> = (0..1000).map(|i| census.track(i)).collect();
+        objs.retain(|obj| **obj % 7 != 0);
+        let mut sum = 0;
+        census.for_each(|&i| sum += i);
+        let list_sum: usize = census.list().iter().map(|obj| **obj).sum();
+        assert_eq!(sum, list_sum);
+        assert_eq!(sum, objs.iter().map(|obj| **obj).sum::<usize>());
+    }
+
+    fn gc_passes_with_hint(hint: AccessHint) -> u64 {
+        let census = Inventory::new();
+        census.optimize_for(hint);
+        let mut objs: Vec<_> = (0..100).map(|i| census.track(i)).collect();
+        // Drop a third of the objects, one at a time.
+        for _ in 0..33 {
+            objs.pop();
+        }
+        census.len();
+        census.stats().gc_passes
+    }
+
+    #[test]
+    fn test_census_optimize_for() {
+        // A third of dead references is enough to collect them only when reads dominate.
+        assert!(gc_passes_with_hint(AccessHint::ReadHeavy) > 0);
+        assert_eq!(gc_passes_with_hint(AccessHint::Balanced), 0);
+        assert_eq!(gc_passes_with_hint(AccessHint::WriteHeavy), 0);
+
+        let census = Inventory::new();
+        census.optimize_for(AccessHint::WriteHeavy);
+        assert_eq!(census.lock_items().wait_strategy, WaitStrategy::Park);
+        let one = census.track(1);
+        let census_clone = census.clone();
+        let handle = thread::spawn(move || census_clone.wait_until_empty());
+        while census.lock_items().parked_threads.is_empty() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        // Switching back wakes up the parked waiter all the same.
+        census.optimize_for(AccessHint::Balanced);
+        assert_eq!(census.lock_items().wait_strategy, WaitStrategy::Condvar);
+        drop(one);
+        handle.join().unwrap();
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_tracked_object_serialize() {
+        let census = Inventory::new();
+        let objs: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|s| census.track(s.to_string()))
+            .collect();
+        let json = serde_json::to_string(&census.list()).unwrap();
+        let mut values: Vec<String> = serde_json::from_str(&json).unwrap();
+        values.sort();
+        assert_eq!(values, vec!["a", "b", "c"]);
+        drop(objs);
+    }
+
+    #[cfg(feature = "deterministic-order")]
+    #[test]
+    fn test_census_deterministic_order() {
+        let census = Inventory::new();
+        let mut objs: Vec<_> = (0..100).map(|i| census.track(i)).collect();
+        objs.retain(|obj| **obj % 5 == 0);
+        let _last = census.track(100);
+        let values: Vec<usize> = census.list().iter().map(|obj| **obj).collect();
+        let expected: Vec<usize> = (0..=100).filter(|i| i % 5 == 0).collect();
+        assert!(census.stats().gc_passes > 0);
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn test_census_estimated_memory() {
+        let census = Inventory::new();
+        let empty = census.estimated_memory();
+        let mut objs: Vec<_> = (0..100).map(|i| census.track([i; 16])).collect();
+        let full = census.estimated_memory();
+        assert!(full >= empty + 100 * 16 * std::mem::size_of::<usize>());
+        objs.truncate(10);
+        census.compact();
+        let after_gc = census.estimated_memory();
+        assert!(after_gc < full);
+        assert!(after_gc > empty);
+    }
+
+    #[test]
+    fn test_census_observer() {
+        let census = Inventory::new();
+        let (send, recv) = channel();
+        let send = Mutex::new(send);
+        let census_clone = census.clone();
+        census.set_observer(Box::new(move |event: CensusEvent<usize>| {
+            // The observer is called without holding the lock.
+            let len = census_clone.len();
+            let event = match event {
+                CensusEvent::Birth { id, object } => {
+                    assert_eq!(object.id(), id);
+                    ("birth", id, len)
+                }
+                CensusEvent::Death { id } => ("death", id, len),
+            };
+            send.lock().unwrap().send(event).unwrap();
+        }));
+        let one = census.track(1);
+        let id = one.id();
+        drop(one);
+        let batch = census.track_batch(vec![2, 3]);
+        let batch_ids = (batch.get(0).unwrap().id(), batch.get(1).unwrap().id());
+        drop(batch);
+        let events: Vec<_> = recv.try_iter().collect();
+        assert_eq!(
+            events,
+            vec![
+                ("birth", id, 1),
+                ("death", id, 0),
+                ("birth", batch_ids.0, 2),
+                ("birth", batch_ids.1, 2),
+                ("death", batch_ids.0, 0),
+                ("death", batch_ids.1, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_concurrent_lists_do_not_block_each_other() {
+        let census = Inventory::new();
+        let _objs: Vec<_> = (0..10).map(|i| census.track(i)).collect();
+        // A reader holding the lock must not prevent others from listing.
+        let _read_guard = census.inner.items.read().unwrap();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let census = census.clone();
+                thread::spawn(move || census.list().len())
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 10);
+        }
+    }
+
+    #[test]
+    fn test_condvar_waiters_woken_up_with_rw_lock() {
+        let census = Inventory::builder()
+            .wait_strategy(WaitStrategy::Condvar)
+            .build();
+        let obj = census.track(1);
+        let census_clone = census.clone();
+        let waiter = thread::spawn(move || census_clone.wait_until_empty());
+        while census.read_items().condvar_waiters == 0 {
+            thread::yield_now();
+        }
+        drop(obj);
+        waiter.join().unwrap();
+        assert!(census.is_empty());
+    }
+
+    #[test]
+    fn test_condvar_waiters_count() {
+        let census = Inventory::builder()
+            .wait_strategy(WaitStrategy::Condvar)
+            .build();
+        let obj = census.track(1);
+        assert!(!census.wait_until_empty_timeout(Duration::from_millis(5)));
+        assert_eq!(census.read_items().condvar_waiters, 0);
+        let waiters: Vec<_> = (0..4)
+            .map(|_| {
+                let census = census.clone();
+                thread::spawn(move || census.wait_until_empty())
+            })
+            .collect();
+        while census.read_items().condvar_waiters < 4 {
+            thread::yield_now();
+        }
+        drop(obj);
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+        assert_eq!(census.read_items().condvar_waiters, 0);
+    }
+
+    #[test]
+    fn test_with_capacity_does_not_reallocate() {
+        let census = Inventory::with_capacity(1_000);
+        let capacity = census.stats().capacity;
+        assert!(capacity >= 1_000);
+        let objs: Vec<_> = (0..1_000).map(|i| census.track(i)).collect();
+        assert_eq!(census.stats().capacity, capacity);
+        assert_eq!(census.len(), objs.len());
+    }
 
-impl<T: fmt::Debug> fmt::Debug for TrackedObject<T> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        write!(f, "Tracked({:?})", self.inner.item)
+    #[test]
+    fn test_list_filtered() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..10).map(|i| census.track(i)).collect();
+        assert!(census.list_filtered(|_| false).is_empty());
+        let values = |objs: Vec<TrackedObject<i32>>| -> Vec<i32> {
+            let mut values: Vec<i32> = objs.iter().map(|obj| **obj).collect();
+            values.sort();
+            values
+        };
+        assert_eq!(values(census.list_filtered(|&i| i < 3)), vec![0, 1, 2]);
+        assert_eq!(census.list_filtered(|_| true).len(), 10);
+        // The returned handles keep the matching objects alive.
+        let small = census.list_filtered(|&i| i < 3);
+        drop(objs);
+        assert_eq!(census.len(), 3);
+        drop(small);
+        assert!(census.is_empty());
     }
-}
 
-impl<T> TrackedObject<T> {
-    /// Creates a new object from an existing one.
-    ///
-    /// The new object will be registered
-    /// in your original object's inventory.
-    ///
-    /// ```rust
-    /// use census::{Inventory, TrackedObject};
-    ///
-    /// let inventory = Inventory::new();
-    ///
-    /// let seven = inventory.track(7);
-    /// let fourteen = seven.map(|i| i * 2);
-    /// assert_eq!(*fourteen, 14);
-    ///
-    /// let living_instances = inventory.list();
-    /// assert_eq!(living_instances.len(), 2);
-    /// ```
-    pub fn map<F>(&self, f: F) -> TrackedObject<T>
-    where
-        F: FnOnce(&T) -> T,
-    {
-        let t = f(self);
-        self.inner.census.track(t)
+    #[test]
+    fn test_find() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..10).map(|i| census.track(i)).collect();
+        assert_eq!(
+            census.find(|&i| i == 4).map(|obj| obj.id()),
+            Some(objs[4].id())
+        );
+        assert!(census.find(|&i| i == 10).is_none());
+        let mut objs = objs;
+        objs.remove(4);
+        assert!(census.find(|&i| i == 4).is_none());
     }
-}
 
-impl<T> Drop for InnerTrackedObject<T> {
-    fn drop(&mut self) {
-        let mut lock = self.census.lock_items();
-        lock.record_death();
-        self.census.inner.condvar.notify_all();
+    #[test]
+    fn test_tracked_object_age() {
+        let census = Inventory::new();
+        let one = census.track(1);
+        thread::sleep(Duration::from_millis(20));
+        assert!(one.age() >= Duration::from_millis(20));
+        assert_eq!(one.clone().created_at(), one.created_at());
+        let two = one.map(|i| i + 1);
+        assert!(two.created_at() > one.created_at());
+        assert!(two.age() < one.age());
     }
-}
 
-impl<T> Deref for TrackedObject<T> {
-    type Target = T;
+    #[test]
+    fn test_list_older_than() {
+        let census = Inventory::new();
+        let one = census.track(1);
+        let two = census.track(2);
+        thread::sleep(Duration::from_millis(50));
+        let _three = census.track(3);
+        let mut old = census.list_older_than(Duration::from_millis(50));
+        old.sort_by_key(|obj| obj.id());
+        assert_eq!(old.len(), 2);
+        assert!(old[0].ptr_eq(&one));
+        assert!(old[1].ptr_eq(&two));
+    }
 
-    fn deref(&self) -> &T {
-        &self.inner.item
+    #[test]
+    fn test_weak_inventory() {
+        let census = Inventory::new();
+        let census_clone = census.clone();
+        let weak = census.downgrade();
+        let obj = census.track(1);
+        assert_eq!(weak.upgrade().map(|census| census.len()), Some(1));
+        drop(census);
+        drop(census_clone);
+        // The living object still holds the inventory.
+        assert!(weak.upgrade().is_some());
+        drop(obj);
+        assert!(weak.upgrade().is_none());
+        assert!(weak.clone().upgrade().is_none());
     }
-}
 
-impl<T> AsRef<T> for TrackedObject<T> {
-    fn as_ref(&self) -> &T {
-        &self.inner.item
+    #[test]
+    fn test_gc_ratio() {
+        let items_len_after_drops = |ratio: f64| {
+            let census = Inventory::new();
+            census.set_gc_ratio(ratio);
+            let objs: Vec<_> = (0..100).map(|i| census.track(i)).collect();
+            let _kept = objs[..70].to_vec();
+            drop(objs);
+            census.len();
+            let items_len = census.read_items().items.len();
+            items_len
+        };
+        assert_eq!(items_len_after_drops(0.5), 100);
+        assert!(items_len_after_drops(0.25) < 100);
     }
-}
 
-impl<T> Borrow<T> for TrackedObject<T> {
-    fn borrow(&self) -> &T {
-        &self.inner.item
+    #[test]
+    fn test_gc() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..100).map(|i| census.track(i)).collect();
+        let kept = objs[..60].to_vec();
+        drop(objs);
+        // 60 alive out of 100 references do not trigger the lazy collection.
+        assert_eq!(census.read_items().items.len(), 100);
+        census.gc();
+        assert_eq!(census.read_items().items.len(), 60);
+        assert_eq!(census.len(), 60);
+        drop(kept);
+        census.gc();
+        assert!(census.read_items().items.is_empty());
     }
-}
 
-#[cfg(test)]
-mod tests {
+    #[test]
+    #[should_panic(expected = "ratio must be between 0 and 1")]
+    fn test_gc_ratio_out_of_range() {
+        Inventory::<u32>::new().set_gc_ratio(1.5);
+    }
 
-    use super::Inventory;
-    use std::sync::mpsc::channel;
-    use std::sync::{Arc, Barrier};
-    use std::thread;
+    #[test]
+    fn test_shrink_to_fit() {
+        let census = Inventory::with_capacity(10_000);
+        let objs: Vec<_> = (0..10_000).map(|i| census.track(i)).collect();
+        let kept = objs[..10].to_vec();
+        drop(objs);
+        census.shrink_to_fit();
+        let stats = census.stats();
+        assert_eq!(stats.alive, kept.len());
+        assert!(stats.capacity >= kept.len());
+        assert!(stats.capacity < 100);
+    }
 
     #[test]
-    fn test_census_map() {
+    fn test_census_total_tracked() {
         let census = Inventory::new();
-        let a = census.track(1);
-        let _b = a.map(|v| v * 7);
-        assert_eq!(census.len(), 2);
-        assert_eq!(
-            census.list().into_iter().map(|m| *m).collect::<Vec<_>>(),
-            vec![1, 7]
-        );
+        for round in 1..=3 {
+            let objs: Vec<_> = (0..10).map(|i| census.track(i)).collect();
+            let _mapped = objs[0].map(|i| i + 1);
+            drop(objs);
+            assert_eq!(census.total_tracked(), round * 11);
+        }
+        assert!(census.is_empty());
     }
 
     #[test]
-    fn test_census() {
+    fn test_census_peak_alive() {
         let census = Inventory::new();
-        let _a = census.track(1);
-        let _b = census.track(3);
-        assert_eq!(census.len(), 2,);
+        let mut objs = Vec::new();
+        let mut max_alive = 0;
+        for i in 0..50 {
+            objs.push(census.track(i));
+            max_alive = max_alive.max(objs.len());
+            if i % 3 == 0 {
+                objs.truncate(objs.len() / 2);
+            }
+            assert_eq!(census.peak_alive(), max_alive);
+        }
+        objs.truncate(1);
+        assert_eq!(census.len(), 1);
+        assert_eq!(census.peak_alive(), max_alive);
+        assert!(max_alive > 1);
+    }
+
+    #[test]
+    fn test_census_iter() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..10).map(|i| census.track(i)).collect();
+        assert_eq!(census.iter().filter(|obj| **obj < 3).count(), 3);
         assert_eq!(
-            census.list().into_iter().map(|m| *m).collect::<Vec<_>>(),
-            vec![1, 3]
+            census.iter().filter(|obj| **obj % 3 == 0).count(),
+            census
+                .list()
+                .into_iter()
+                .filter(|obj| **obj % 3 == 0)
+                .count()
         );
+        assert_eq!(census.iter().len(), 10);
+        let snapshot = census.snapshot();
+        assert_eq!(snapshot.len(), 10);
+        assert_eq!((&snapshot).into_iter().count(), 10);
+        drop(snapshot);
+        let ids: Vec<u64> = census.iter().map(|obj| obj.id()).collect();
+        let list_ids: Vec<u64> = census.list().into_iter().map(|obj| obj.id()).collect();
+        assert_eq!(ids, list_ids);
+        // The snapshot keeps the objects alive until they are yielded.
+        let mut iter = census.iter().skip(5);
+        drop(objs);
+        assert_eq!(census.len(), 10);
+        assert_eq!(iter.next().map(|obj| *obj), Some(5));
+        drop(iter);
+        assert!(census.is_empty());
     }
 
     #[test]
-    fn test_census_2() {
+    fn test_census_track_all() {
         let census = Inventory::new();
-        {
-            let _a = census.track(1);
-            let _b = census.track(3);
-            // dropping both here
+        let version_before = census.stats().version;
+        let objs = census.track_all(vec!["a", "b", "c", "d", "e"]);
+        let values: Vec<&str> = objs.iter().map(|obj| **obj).collect();
+        assert_eq!(values, vec!["a", "b", "c", "d", "e"]);
+        assert!(objs.windows(2).all(|pair| pair[0].id() < pair[1].id()));
+        assert_eq!(census.list().len(), 5);
+        assert_eq!(census.stats().version, version_before + 5);
+        drop(objs);
+        assert!(census.is_empty());
+    }
+
+    #[test]
+    fn test_count_matching() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..10).map(|i| census.track(i)).collect();
+        assert_eq!(census.count_matching(|_| false), 0);
+        assert_eq!(census.count_matching(|&i| i % 2 == 1), 5);
+        assert_eq!(census.count_matching(|_| true), 10);
+        drop(objs);
+        assert!(census.is_empty());
+        assert_eq!(census.count_matching(|_| true), 0);
+    }
+
+    #[test]
+    fn test_census_trait_objects() {
+        trait Shape: Send + Sync {
+            fn area(&self) -> u32;
         }
-        assert_eq!(census.len(), 0);
-        assert!(census.list().is_empty());
+        struct Square(u32);
+        struct Rectangle(u32, u32);
+        impl Shape for Square {
+            fn area(&self) -> u32 {
+                self.0 * self.0
+            }
+        }
+        impl Shape for Rectangle {
+            fn area(&self) -> u32 {
+                self.0 * self.1
+            }
+        }
+        let census: Inventory<Box<dyn Shape>> = Inventory::new();
+        let square = census.track(Box::new(Square(3)));
+        let _rectangle = census.track(Box::new(Rectangle(2, 5)));
+        let total_area: u32 = census.list().iter().map(|shape| shape.area()).sum();
+        assert_eq!(total_area, 19);
+        drop(square);
+        assert_eq!(census.iter().map(|shape| shape.area()).sum::<u32>(), 10);
     }
 
     #[test]
-    fn test_census_3() {
+    fn test_census_track_mut() {
         let census = Inventory::new();
-        let a = census.track(1);
-        let _a2 = a.clone();
-        drop(a);
+        let counter = census.track_mut(1);
+        let counter_clone = counter.clone();
+        *counter.write() += 1;
+        assert_eq!(*counter_clone.read(), 2);
+        *counter_clone.lock() *= 3;
+        assert_eq!(*counter.read(), 6);
+        assert_eq!(counter_clone.id(), counter.id());
+        let _other = census.track_mut(10);
+        assert_eq!(census.len(), 2);
+        let mut values: Vec<i32> = census
+            .list()
+            .iter()
+            .map(|obj| *obj.read().unwrap())
+            .collect();
+        values.sort();
+        assert_eq!(values, vec![6, 10]);
+        drop(counter);
+        drop(counter_clone);
         assert_eq!(census.len(), 1);
-        assert_eq!(
-            census.list().into_iter().map(|m| *m).collect::<Vec<_>>(),
-            vec![1]
-        );
     }
 
     #[test]
-    fn test_census_list_extends_life() {
+    fn test_try_into_inner() {
         let census = Inventory::new();
-        let a = census.track(1);
-        let living = census.list();
-        assert_eq!(living.len(), 1);
-        drop(a);
-        let living_2 = census.list();
-        assert_eq!(living_2.len(), 1);
-        drop(living_2);
-        drop(living);
-        assert_eq!(census.len(), 0);
+        let one = census.track(1);
+        let _two = census.track(2);
+        let one_clone = one.clone();
+        let one = one.try_into_inner().unwrap_err();
+        assert_eq!(census.len(), 2);
+        assert_eq!(census.total_dropped(), 0);
+        drop(one_clone);
+        assert_eq!(census.len(), 2);
+        assert_eq!(one.try_into_inner().ok(), Some(1));
+        assert_eq!(census.len(), 1);
+        assert_eq!(census.total_dropped(), 1);
+        assert_eq!(census.list().len(), 1);
+    }
+
+    #[test]
+    fn test_census_drain() {
+        let census = Inventory::new();
+        let objs: Vec<_> = (0..5).map(|i| census.track(i)).collect();
+        assert_eq!(census.list_cached().len(), 5);
+        let drained = census.drain();
+        assert_eq!(drained.len(), 5);
         assert!(census.list().is_empty());
+        assert!(census.list_cached().is_empty());
+        assert_eq!(census.len(), 5);
+        let _six = census.track(6);
+        assert_eq!(census.list().len(), 1);
+        drop(objs);
+        assert_eq!(census.len(), 6);
+        drop(drained);
+        assert_eq!(census.len(), 1);
+        assert_eq!(census.total_dropped(), 5);
     }
 
+    #[cfg(feature = "tracing")]
     #[test]
-    fn test_census_race_condition() {
+    fn test_census_tracing_events() {
+        use std::fmt::Debug;
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        #[derive(Default)]
+        struct Fields(Vec<String>);
+
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                self.0.push(format!("{}={:?}", field.name(), value));
+            }
+        }
+
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+
+        impl Subscriber for Recorder {
+            fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _span: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+            fn record(&self, _span: &Id, _values: &Record<'_>) {}
+            fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields::default();
+                event.record(&mut fields);
+                self.0.lock().unwrap().push(fields.0.join(" "));
+            }
+            fn enter(&self, _span: &Id) {}
+            fn exit(&self, _span: &Id) {}
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        tracing::subscriber::with_default(Recorder(events.clone()), || {
+            let census = Inventory::new();
+            let one = census.track(1);
+            let id = one.id();
+            drop(one);
+            assert_eq!(
+                *events.lock().unwrap(),
+                vec![
+                    format!("message=object tracked id={} alive=1", id),
+                    format!("message=object dropped id={} alive=0", id),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn test_census_wait_until_at_least() {
         let census = Inventory::new();
         let census_clone = census.clone();
-        thread::spawn(move || {
-            for _ in 0..1_000 {
-                let _a = census_clone.track(1);
-            }
+        let handle = thread::spawn(move || {
+            (0..5)
+                .map(|i| {
+                    thread::sleep(Duration::from_millis(2));
+                    census_clone.track(i)
+                })
+                .collect::<Vec<_>>()
         });
-        for i in 0..10_000 {
-            println!("i {}", i);
-            census.list();
-        }
+        census.wait_until_at_least(5);
+        assert!(census.len() >= 5);
+        drop(handle.join().unwrap());
     }
 
     #[test]
-    fn test_census_concurrent_drop() {
+    fn test_census_wait_until_at_most() {
         let census = Inventory::new();
-        let mut senders = Vec::new();
-        let mut handles = Vec::new();
-        let barrier = Arc::new(Barrier::new(2));
-        for _ in 0..2 {
-            let (send, recv) = channel();
-            let barrier = barrier.clone();
-            handles.push(thread::spawn(move || {
-                for obj in recv {
-                    barrier.wait();
-                    drop(obj);
-                }
-            }));
-            senders.push(send);
-        }
-        for i in 0..50_000 {
-            let tracked = census.track(i);
-            for send in &senders {
-                send.send(tracked.clone()).unwrap();
+        let mut objs: Vec<_> = (0..5).map(|i| census.track(i)).collect();
+        let handle = thread::spawn(move || {
+            while objs.pop().is_some() {
+                thread::sleep(Duration::from_millis(2));
             }
-        }
-        drop(senders);
+        });
+        census.wait_until_at_most(2);
+        assert!(census.len() <= 2);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_census_contains() {
+        let census = Inventory::new();
+        let other = Inventory::new();
+        let child = census.new_child();
+        let one = census.track(1);
+        let two = other.track(2);
+        let three = child.track(3);
+        assert!(census.contains(&one));
+        assert!(census.contains(&one.clone()));
+        assert!(!census.contains(&two));
+        assert!(other.contains(&two));
+        assert!(census.contains(&three));
+        assert!(child.contains(&three));
+        assert!(!child.contains(&one));
+    }
+
+    #[test]
+    fn test_census_track_deduped() {
+        let census = Inventory::new();
+        let a = census.track_deduped("a".to_string());
+        let a_again = census.track_deduped("a".to_string());
+        assert!(a.ptr_eq(&a_again));
+        assert_eq!(census.len(), 1);
+        let b = census.track_deduped("b".to_string());
+        assert!(!a.ptr_eq(&b));
+        assert_eq!(census.len(), 2);
+        drop(a);
+        drop(a_again);
+        let new_a = census.track_deduped("a".to_string());
+        assert_eq!(census.len(), 2);
+        assert_eq!(census.stats().total_tracked, 3);
+        drop((b, new_a));
+        assert!(census.is_empty());
+    }
+
+    #[test]
+    fn test_census_track_deduped_concurrent() {
+        let census = Inventory::new();
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let census = census.clone();
+                thread::spawn(move || {
+                    (0..50)
+                        .map(|i| census.track_deduped(i % 5))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let objs: Vec<_> = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+        assert_eq!(objs.len(), 400);
+        assert_eq!(census.len(), 5);
+    }
+
+    #[test]
+    fn test_census_count_by() {
+        let census = Inventory::new();
+        let objs: Vec<_> = [("a", 1), ("b", 2), ("a", 3), ("c", 4), ("b", 5)]
+            .iter()
+            .map(|&request| census.track(request))
+            .collect();
+        let counts = census.count_by(|request| request.0);
+        let expected: HashMap<&str, usize> =
+            vec![("a", 2), ("b", 2), ("c", 1)].into_iter().collect();
+        assert_eq!(counts, expected);
+        let mut objs = objs;
+        objs.remove(3);
+        let counts = census.count_by(|request| request.0);
+        assert_eq!(counts.get("c"), None);
+        assert_eq!(counts.len(), 2);
+        drop(objs);
+        assert!(census.is_empty());
+    }
+
+    #[test]
+    fn test_census_try_map() {
+        let census = Inventory::new();
+        let one = census.track(1);
+        let stats_before = census.stats();
+        assert_eq!(
+            one.try_map(|_| Err::<i32, &str>("failed")).unwrap_err(),
+            "failed"
+        );
+        assert_eq!(census.stats(), stats_before);
+        assert_eq!(census.read_items().items.len(), 1);
+        let two = one.try_map(|i| Ok::<i32, ()>(i + 1)).unwrap();
+        assert_eq!(two.parent_id(), Some(one.id()));
+        let mut values: Vec<i32> = census.list().iter().map(|obj| **obj).collect();
+        values.sort();
+        assert_eq!(values, vec![1, 2]);
+    }
+
+    #[test]
+    #[allow(clippy::mutable_key_type)]
+    fn test_tracked_object_identity() {
+        use std::collections::HashSet;
+        struct NotHash(u32);
+        let census = Inventory::new();
+        let one = census.track(NotHash(1));
+        let other_one = census.track(NotHash(1));
+        let one_again = census
+            .list()
+            .into_iter()
+            .find(|obj| obj.id() == one.id())
+            .unwrap();
+        let mut set = HashSet::new();
+        assert!(set.insert(one_again));
+        assert!(set.contains(&one));
+        assert!(!set.contains(&other_one));
+        assert!(!set.insert(one));
+        assert_eq!(set.len(), 1);
+        assert_eq!(set.iter().next().map(|obj| obj.0), Some(1));
+        let values = Inventory::new();
+        let two = values.track(2);
+        let three = two.map(|i| i + 1);
+        assert!(two == two.clone());
+        assert!(two != three);
+    }
+
+    #[test]
+    fn test_census_scope() {
+        let census = Inventory::new();
+        let kept = {
+            let scope = census.scope();
+            let one = scope.track(1);
+            scope.track(2);
+            scope.track(3);
+            assert_eq!(scope.len(), 3);
+            assert_eq!(census.len(), 3);
+            let kept = one.clone();
+            drop(one);
+            assert_eq!(census.len(), 3);
+            kept
+        };
+        assert_eq!(census.len(), 1);
+        assert_eq!(*kept, 1);
+        drop(kept);
+        assert!(census.is_empty());
+    }
+
+    #[test]
+    fn test_census_snapshot_ids() {
+        use std::collections::HashSet;
+        let census = Inventory::new();
+        let mut objs: Vec<_> = (0..3).map(|i| census.track(i)).collect();
+        let before: HashSet<u64> = census.snapshot_ids().into_iter().collect();
+        assert_eq!(before.len(), 3);
+        let dropped = objs.remove(1);
+        let dropped_id = dropped.id();
+        drop(dropped);
+        let after: HashSet<u64> = census.snapshot_ids().into_iter().collect();
+        assert_eq!(
+            before.difference(&after).collect::<Vec<_>>(),
+            vec![&dropped_id]
+        );
+        assert!(after.is_subset(&before));
+        drop(objs);
+        assert!(census.snapshot_ids().is_empty());
+    }
+
+    #[test]
+    fn test_census_list_into() {
+        let census = Inventory::new();
+        let mut objs: Vec<_> = (0..3).map(|i| census.track(i)).collect();
+        let mut buf = Vec::with_capacity(16);
+        census.list_into(&mut buf);
+        assert_eq!(buf.len(), 3);
+        objs.remove(0);
+        // The buffer still holds the removed object.
+        assert_eq!(census.len(), 3);
+        census.list_into(&mut buf);
+        assert_eq!(buf.len(), 2);
+        assert_eq!(buf.capacity(), 16);
+        assert_eq!(census.len(), 2);
+        let ids: Vec<u64> = buf.iter().map(|obj| obj.id()).collect();
+        let list_ids: Vec<u64> = census.list().iter().map(|obj| obj.id()).collect();
+        assert_eq!(ids, list_ids);
+    }
+
+    #[test]
+    fn test_census_debug() {
+        struct NotDebug;
+        let census = Inventory::with_name("sessions");
+        let objs: Vec<_> = (0..7).map(|_| census.track(NotDebug)).collect();
+        let debug = format!("{:?}", census);
+        assert_eq!(
+            debug,
+            r#"Inventory { name: "sessions", alive: 7, slots: 7 }"#
+        );
+        drop(objs);
+        assert!(format!("{:?}", census).contains("alive: 0"));
+    }
+
+    #[test]
+    fn test_list_cached_served_under_read_lock() {
+        let census = Inventory::new();
+        let _objs: Vec<_> = (0..3).map(|i| census.track(i)).collect();
+        let cached = census.list_cached();
+        // A valid cache is served without taking the write lock.
+        let _read_guard = census.inner.items.read().unwrap();
+        let census_clone = census.clone();
+        let from_thread = thread::spawn(move || census_clone.list_cached())
+            .join()
+            .unwrap();
+        assert!(Arc::ptr_eq(&cached, &from_thread));
+    }
+
+    #[test]
+    fn test_slab_storage() {
+        let census = Inventory::builder().slab(true).build();
+        let one = census.track(1);
+        let two = census.track(2);
+        let two_clone = two.clone();
+        let two_ptr = Handle::as_ptr(&two.inner);
+        drop(two);
+        assert_eq!(census.len(), 2);
+        drop(two_clone);
+        assert_eq!(census.len(), 1);
+        // `three` reuses the slot of `two`, with a new generation.
+        let three = census.track(3);
+        assert_eq!(Handle::as_ptr(&three.inner), two_ptr);
+        let values: Vec<i32> = census.list().iter().map(|obj| **obj).collect();
+        assert_eq!(values.len(), 2);
+        assert!(values.contains(&1) && values.contains(&3));
+        assert_eq!(three.try_into_inner(), Ok(3));
+        assert_eq!(census.reclaim(vec![one]), vec![1]);
+        assert!(census.is_empty());
+        assert!(census.list().is_empty());
+    }
+
+    #[test]
+    fn test_slab_storage_child_outlived_by_parent() {
+        let parent = Inventory::builder().slab(true).build();
+        let child = parent.new_child();
+        let one = child.track(1);
+        let _two = parent.track(2);
+        assert_eq!(parent.len(), 2);
+        drop(child);
+        assert_eq!(parent.len(), 2);
+        drop(one);
+        assert_eq!(parent.len(), 1);
+        assert_eq!(*parent.list()[0], 2);
+    }
+
+    #[test]
+    fn test_slab_storage_contended_track_and_drop() {
+        let census = Inventory::builder().slab(true).build();
+        let start = Arc::new(Barrier::new(8));
+        let handles: Vec<_> = (0..8)
+            .map(|thread_id| {
+                let census = census.clone();
+                let start = start.clone();
+                thread::spawn(move || {
+                    start.wait();
+                    let mut kept = Vec::new();
+                    for i in 0..5_000 {
+                        let obj = census.track(thread_id * 10_000 + i);
+                        if i % 10 == 0 {
+                            kept.push(obj);
+                        } else if i % 7 == 0 {
+                            assert!(census.list().len() >= kept.len());
+                        }
+                    }
+                    assert!(kept
+                        .iter()
+                        .enumerate()
+                        .all(|(k, obj)| **obj == thread_id * 10_000 + 10 * k));
+                })
+            })
+            .collect();
         for handle in handles {
             handle.join().unwrap();
         }
-        assert_eq!(census.len(), 0);
+        assert!(census.is_empty());
+        assert_eq!(census.stats().total_tracked, 40_000);
     }
 
     fn test_census_changes_iter_util(el: usize) {
@@ -0,0 +1,466 @@
+//! Slab storage for the tracked objects, see `InventoryBuilder::slab`.
+//!
+//! The slots of a slab are allocated by pages which never move, so that
+//! a reference to a slot stays valid for as long as the slab is alive.
+//! Page `k` holds `FIRST_PAGE_LEN << k` slots: a few pages are enough to
+//! hold millions of objects.
+//!
+//! Each slot packs a generation and the number of strong handles to its
+//! value in a single atomic. The generation is bumped every time the slot
+//! is freed, so that a `SlabWeak`, which only knows the index and the
+//! generation of its slot, tells a dead value from the value of a newer
+//! object stored in the same slot.
+//!
+//! `Handle` and `WeakHandle` hide whether an object lives in a slab or in
+//! its own `Arc` allocation.
+
+use alloc::boxed::Box;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop, MaybeUninit};
+use core::ops::Deref;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::Ordering;
+
+use sync::atomic::{fence, AtomicPtr, AtomicU64};
+use sync::{self, Mutex};
+
+/// Number of slots of the first page of a slab.
+const FIRST_PAGE_LEN: usize = 32;
+
+/// Maximum number of pages of a slab. The slot indices of a full slab
+/// still fit in a `u32`.
+const MAX_PAGES: usize = 27;
+
+const COUNT_MASK: u64 = u32::MAX as u64;
+
+/// Above this number of strong handles, the process is aborted rather
+/// than risking an overflow of the count into the generation.
+const MAX_COUNT: u64 = COUNT_MASK / 2;
+
+/// Aborts the process, as an overflowing count cannot be unwound safely.
+#[cfg(feature = "std")]
+fn abort() -> ! {
+    std::process::abort()
+}
+
+/// Aborts the process, as an overflowing count cannot be unwound safely.
+#[cfg(not(feature = "std"))]
+fn abort() -> ! {
+    // Panicking while panicking aborts.
+    struct PanicOnDrop;
+
+    impl Drop for PanicOnDrop {
+        fn drop(&mut self) {
+            panic!("The count of handles to a slot overflowed.");
+        }
+    }
+
+    let _panic_on_drop = PanicOnDrop;
+    panic!("The count of handles to a slot overflowed.");
+}
+
+fn generation(state: u64) -> u32 {
+    (state >> 32) as u32
+}
+
+fn count(state: u64) -> u64 {
+    state & COUNT_MASK
+}
+
+fn page_len(page: usize) -> usize {
+    FIRST_PAGE_LEN << page
+}
+
+/// Returns the page holding the slot `index`, and the offset of the slot in it.
+fn locate(index: usize) -> (usize, usize) {
+    let bucket = index / FIRST_PAGE_LEN + 1;
+    let page = (usize::BITS - 1 - bucket.leading_zeros()) as usize;
+    (page, index - FIRST_PAGE_LEN * ((1 << page) - 1))
+}
+
+struct Slot<V> {
+    // Generation in the high 32 bits, number of strong handles in the low 32 bits.
+    state: AtomicU64,
+    index: usize,
+    // Set while the slot holds a value, so that the slab outlives its values.
+    owner: UnsafeCell<Option<Arc<Slab<V>>>>,
+    value: UnsafeCell<MaybeUninit<V>>,
+}
+
+struct FreeSlots {
+    indices: Vec<usize>,
+    // Number of slots handed out so far.
+    len: usize,
+}
+
+/// Pages of slots, holding values of type `V`.
+pub struct Slab<V> {
+    pages: Box<[AtomicPtr<Slot<V>>]>,
+    free: Mutex<FreeSlots>,
+    // The slab shares its values between threads, as an `Arc` would.
+    _values: PhantomData<Arc<V>>,
+}
+
+impl<V> Slab<V> {
+    pub fn new() -> Slab<V> {
+        Slab {
+            pages: (0..MAX_PAGES)
+                .map(|_| AtomicPtr::new(ptr::null_mut()))
+                .collect(),
+            free: Mutex::new(FreeSlots {
+                indices: Vec::new(),
+                len: 0,
+            }),
+            _values: PhantomData,
+        }
+    }
+
+    /// Stores `value` in a free slot, allocating a new page if needed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slab is full.
+    pub fn insert(self: &Arc<Self>, value: V) -> SlabArc<V> {
+        let slot = {
+            let mut free = sync::lock(&self.free);
+            let index = free.indices.pop().unwrap_or(free.len);
+            let (page, offset) = locate(index);
+            assert!(page < MAX_PAGES, "The slab of the inventory is full.");
+            if index == free.len {
+                free.len += 1;
+            }
+            let mut slots = self.pages[page].load(Ordering::Acquire);
+            if slots.is_null() {
+                slots = alloc_page(page);
+                self.pages[page].store(slots, Ordering::Release);
+            }
+            // Safety: the page holds `page_len(page)` slots, and is only
+            // freed with the slab.
+            unsafe { &*slots.add(offset) }
+        };
+        // Safety: the slot is free, so that no other thread accesses its
+        // value or its owner until its count is incremented.
+        unsafe {
+            (*slot.value.get()).write(value);
+            *slot.owner.get() = Some(Arc::clone(self));
+        }
+        slot.state.fetch_add(1, Ordering::Release);
+        SlabArc {
+            slot: NonNull::from(slot),
+        }
+    }
+
+    /// Returns the slot `index`, which must have been handed out.
+    fn slot(&self, index: usize) -> &Slot<V> {
+        let (page, offset) = locate(index);
+        let slots = self.pages[page].load(Ordering::Acquire);
+        debug_assert!(!slots.is_null());
+        // Safety: the page was allocated when the slot was handed out.
+        unsafe { &*slots.add(offset) }
+    }
+
+    /// Returns the number of bytes allocated for the pages of the slab.
+    pub fn allocated_bytes(&self) -> usize {
+        let slots: usize = (0..MAX_PAGES)
+            .filter(|&page| !self.pages[page].load(Ordering::Acquire).is_null())
+            .map(page_len)
+            .sum();
+        slots * mem::size_of::<Slot<V>>()
+    }
+}
+
+fn alloc_page<V>(page: usize) -> *mut Slot<V> {
+    let first_index = FIRST_PAGE_LEN * ((1 << page) - 1);
+    let slots: Box<[Slot<V>]> = (0..page_len(page))
+        .map(|offset| Slot {
+            state: AtomicU64::new(0),
+            index: first_index + offset,
+            owner: UnsafeCell::new(None),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        })
+        .collect();
+    Box::into_raw(slots) as *mut Slot<V>
+}
+
+impl<V> Drop for Slab<V> {
+    fn drop(&mut self) {
+        for (page, slots) in self.pages.iter().enumerate() {
+            let slots = slots.load(Ordering::Acquire);
+            if !slots.is_null() {
+                // Safety: the page was allocated by `alloc_page` with this length.
+                // Its slots are all free, as the values keep the slab alive.
+                drop(unsafe {
+                    Box::from_raw(ptr::slice_from_raw_parts_mut(slots, page_len(page)))
+                });
+            }
+        }
+    }
+}
+
+/// A strong handle to a value stored in a slab, akin to an `Arc`.
+pub struct SlabArc<V> {
+    slot: NonNull<Slot<V>>,
+}
+
+unsafe impl<V: Send + Sync> Send for SlabArc<V> {}
+unsafe impl<V: Send + Sync> Sync for SlabArc<V> {}
+
+impl<V> SlabArc<V> {
+    fn slot(&self) -> &Slot<V> {
+        // Safety: the value keeps the slab, hence the page of the slot, alive.
+        unsafe { self.slot.as_ref() }
+    }
+
+    pub fn downgrade(this: &Self) -> SlabWeak<V> {
+        let slot = this.slot();
+        // Safety: the owner of a slot is only changed while it has no strong handle.
+        let slab = unsafe { (*slot.owner.get()).clone() };
+        SlabWeak {
+            slab: slab.expect("A slot with a value has an owner."),
+            index: slot.index,
+            generation: generation(slot.state.load(Ordering::Relaxed)),
+        }
+    }
+
+    pub fn as_ptr(this: &Self) -> *const V {
+        this.slot().value.get() as *const V
+    }
+
+    pub fn try_unwrap(this: Self) -> Result<V, Self> {
+        let state = this.slot().state.load(Ordering::Relaxed);
+        if count(state) != 1
+            || this
+                .slot()
+                .state
+                .compare_exchange(state, state - 1, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+        {
+            return Err(this);
+        }
+        let this = ManuallyDrop::new(this);
+        // Safety: the strong handle was the last one.
+        Ok(unsafe { this.take() })
+    }
+
+    pub fn into_inner(this: Self) -> Option<V> {
+        let this = ManuallyDrop::new(this);
+        if !this.release() {
+            return None;
+        }
+        // Safety: the strong handle was the last one.
+        Some(unsafe { this.take() })
+    }
+
+    /// Releases the strong handle, and returns true if it was the last one.
+    fn release(&self) -> bool {
+        if count(self.slot().state.fetch_sub(1, Ordering::Release)) != 1 {
+            return false;
+        }
+        fence(Ordering::Acquire);
+        true
+    }
+
+    /// Moves the value out of the slot, and frees the slot.
+    ///
+    /// # Safety
+    ///
+    /// The last strong handle to the value must have been released,
+    /// and `self` must not be used afterwards.
+    unsafe fn take(&self) -> V {
+        let slot = self.slot();
+        let value = (*slot.value.get()).assume_init_read();
+        let owner = (*slot.owner.get())
+            .take()
+            .expect("A slot with a value has an owner.");
+        let state = slot.state.load(Ordering::Relaxed);
+        slot.state.store(
+            u64::from(generation(state).wrapping_add(1)) << 32,
+            Ordering::Release,
+        );
+        sync::lock(&owner.free).indices.push(slot.index);
+        // `owner` may be the last reference to the slab, and the slot is
+        // not used past this point.
+        drop(owner);
+        value
+    }
+}
+
+impl<V> Clone for SlabArc<V> {
+    fn clone(&self) -> Self {
+        if count(self.slot().state.fetch_add(1, Ordering::Relaxed)) > MAX_COUNT {
+            abort();
+        }
+        SlabArc { slot: self.slot }
+    }
+}
+
+impl<V> Deref for SlabArc<V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        // Safety: the slot holds a value for as long as it has strong handles.
+        unsafe { (*self.slot().value.get()).assume_init_ref() }
+    }
+}
+
+impl<V> Drop for SlabArc<V> {
+    fn drop(&mut self) {
+        if self.release() {
+            // Safety: the strong handle was the last one.
+            drop(unsafe { self.take() });
+        }
+    }
+}
+
+/// A weak handle to a value stored in a slab, akin to a `Weak`.
+///
+/// The generation of the slot would have to wrap around while the weak
+/// handle is kept for it to mistake a newer value for its own.
+pub struct SlabWeak<V> {
+    slab: Arc<Slab<V>>,
+    index: usize,
+    generation: u32,
+}
+
+impl<V> SlabWeak<V> {
+    pub fn upgrade(&self) -> Option<SlabArc<V>> {
+        let slot = self.slab.slot(self.index);
+        let mut state = slot.state.load(Ordering::Relaxed);
+        loop {
+            if generation(state) != self.generation || count(state) == 0 {
+                return None;
+            }
+            if count(state) > MAX_COUNT {
+                abort();
+            }
+            match slot.state.compare_exchange_weak(
+                state,
+                state + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    return Some(SlabArc {
+                        slot: NonNull::from(slot),
+                    })
+                }
+                Err(current) => state = current,
+            }
+        }
+    }
+
+    pub fn strong_count(&self) -> usize {
+        let state = self.slab.slot(self.index).state.load(Ordering::Relaxed);
+        if generation(state) == self.generation {
+            count(state) as usize
+        } else {
+            0
+        }
+    }
+}
+
+impl<V> Clone for SlabWeak<V> {
+    fn clone(&self) -> Self {
+        SlabWeak {
+            slab: self.slab.clone(),
+            index: self.index,
+            generation: self.generation,
+        }
+    }
+}
+
+/// A strong handle to a value living either in its own `Arc` allocation,
+/// or in a slab.
+pub enum Handle<V> {
+    Heap(Arc<V>),
+    Slab(SlabArc<V>),
+}
+
+impl<V> Handle<V> {
+    pub fn downgrade(this: &Self) -> WeakHandle<V> {
+        match *this {
+            Handle::Heap(ref arc) => WeakHandle::Heap(Arc::downgrade(arc)),
+            Handle::Slab(ref slab_arc) => WeakHandle::Slab(SlabArc::downgrade(slab_arc)),
+        }
+    }
+
+    pub fn as_ptr(this: &Self) -> *const V {
+        match *this {
+            Handle::Heap(ref arc) => Arc::as_ptr(arc),
+            Handle::Slab(ref slab_arc) => SlabArc::as_ptr(slab_arc),
+        }
+    }
+
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        ptr::eq(Handle::as_ptr(this), Handle::as_ptr(other))
+    }
+
+    pub fn try_unwrap(this: Self) -> Result<V, Self> {
+        match this {
+            Handle::Heap(arc) => Arc::try_unwrap(arc).map_err(Handle::Heap),
+            Handle::Slab(slab_arc) => SlabArc::try_unwrap(slab_arc).map_err(Handle::Slab),
+        }
+    }
+
+    pub fn into_inner(this: Self) -> Option<V> {
+        match this {
+            Handle::Heap(arc) => Arc::into_inner(arc),
+            Handle::Slab(slab_arc) => SlabArc::into_inner(slab_arc),
+        }
+    }
+}
+
+impl<V> Clone for Handle<V> {
+    fn clone(&self) -> Self {
+        match *self {
+            Handle::Heap(ref arc) => Handle::Heap(arc.clone()),
+            Handle::Slab(ref slab_arc) => Handle::Slab(slab_arc.clone()),
+        }
+    }
+}
+
+impl<V> Deref for Handle<V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        match *self {
+            Handle::Heap(ref arc) => arc,
+            Handle::Slab(ref slab_arc) => slab_arc,
+        }
+    }
+}
+
+/// A weak handle to a value living either in its own `Arc` allocation,
+/// or in a slab.
+pub enum WeakHandle<V> {
+    Heap(Weak<V>),
+    Slab(SlabWeak<V>),
+}
+
+impl<V> WeakHandle<V> {
+    pub fn upgrade(&self) -> Option<Handle<V>> {
+        match *self {
+            WeakHandle::Heap(ref weak) => weak.upgrade().map(Handle::Heap),
+            WeakHandle::Slab(ref slab_weak) => slab_weak.upgrade().map(Handle::Slab),
+        }
+    }
+
+    pub fn strong_count(&self) -> usize {
+        match *self {
+            WeakHandle::Heap(ref weak) => weak.strong_count(),
+            WeakHandle::Slab(ref slab_weak) => slab_weak.strong_count(),
+        }
+    }
+}
+
+impl<V> Clone for WeakHandle<V> {
+    fn clone(&self) -> Self {
+        match *self {
+            WeakHandle::Heap(ref weak) => WeakHandle::Heap(weak.clone()),
+            WeakHandle::Slab(ref slab_weak) => WeakHandle::Slab(slab_weak.clone()),
+        }
+    }
+}
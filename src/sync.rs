@@ -0,0 +1,102 @@
+//! Synchronization primitives used by the inventory.
+//!
+//! When compiled with `--cfg loom`, the primitives are swapped for their
+//! `loom` equivalents, so that the inventory can be checked with loom's
+//! model checker. `Arc` and `Weak` always come from `std`, as `loom`
+//! does not provide `Weak` references: the loom tests do not model the
+//! race between upgrading a `Weak` and dropping the last `Arc`.
+//!
+//! The loom tests can be run with:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --release --lib loom_tests
+//! ```
+//!
+//! Without the `std` feature, the locks are those of the `spin` crate,
+//! and there is no `Condvar`.
+//!
+//! The locks are acquired with the functions of this module, which ignore
+//! poisoning: the state of the inventory is kept consistent even if a
+//! thread panics while holding a lock. Spin locks are never poisoned.
+
+#[cfg(loom)]
+pub use loom::sync::{Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(all(not(loom), not(feature = "std")))]
+pub use spin::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(all(not(loom), feature = "std"))]
+pub use std::sync::{Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[cfg(feature = "std")]
+use std::sync::PoisonError;
+
+pub mod atomic {
+    #[cfg(not(loom))]
+    pub use core::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicU64, AtomicUsize};
+    #[cfg(loom)]
+    pub use loom::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicU64, AtomicUsize};
+}
+
+/// Returns true if a thread panicked while holding `lock` for writing.
+#[cfg(all(not(loom), feature = "std"))]
+pub fn is_poisoned<T>(lock: &RwLock<T>) -> bool {
+    lock.is_poisoned()
+}
+
+/// Returns true if a thread panicked while holding `lock` for writing.
+///
+/// Neither `loom` nor the spin locks track poisoning.
+#[cfg(any(loom, not(feature = "std")))]
+pub fn is_poisoned<T>(_lock: &RwLock<T>) -> bool {
+    false
+}
+
+/// Acquires `lock` for reading.
+#[cfg(feature = "std")]
+pub fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Acquires `lock` for reading.
+///
+/// The spin locks favor readers: writers wait for as long as readers keep
+/// overlapping.
+#[cfg(not(feature = "std"))]
+pub fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read()
+}
+
+/// Acquires `lock` for writing.
+#[cfg(feature = "std")]
+pub fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Acquires `lock` for writing.
+#[cfg(not(feature = "std"))]
+pub fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write()
+}
+
+/// Acquires `mutex`.
+#[cfg(feature = "std")]
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Acquires `mutex`.
+#[cfg(not(feature = "std"))]
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock()
+}
+
+/// Returns the value of `mutex`, which is not shared.
+#[cfg(feature = "std")]
+pub fn get_mut<T>(mutex: &mut Mutex<T>) -> &mut T {
+    mutex.get_mut().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns the value of `mutex`, which is not shared.
+#[cfg(not(feature = "std"))]
+pub fn get_mut<T>(mutex: &mut Mutex<T>) -> &mut T {
+    mutex.get_mut()
+}
@@ -0,0 +1,66 @@
+//! Exercises the inventory from a `no_std` crate, using `alloc` only.
+//!
+//! Run it against the `no_std` build of census with:
+//!
+//! ```text
+//! cargo test --no-default-features --features spin --test no_std
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+extern crate census;
+// Only the test harness and the threads of the last test require `std`.
+extern crate std;
+
+use alloc::vec::Vec;
+use census::Inventory;
+
+#[test]
+fn test_no_std_track_list_len() {
+    let inventory = Inventory::new();
+    let one = inventory.track(1u32);
+    let two = inventory.track(2u32);
+    assert_eq!(inventory.len(), 2);
+    let mut values: Vec<u32> = inventory.list().iter().map(|obj| **obj).collect();
+    values.sort_unstable();
+    assert_eq!(values, [1, 2]);
+    drop(one);
+    assert_eq!(inventory.len(), 1);
+    assert_eq!(*inventory.list()[0], 2);
+    drop(two);
+    assert!(inventory.is_empty());
+    assert!(inventory.list().is_empty());
+}
+
+#[test]
+fn test_no_std_slab_storage() {
+    let inventory = Inventory::builder().slab(true).build();
+    let child = inventory.new_child();
+    let objs: Vec<_> = (0..100u32).map(|i| child.track(i)).collect();
+    assert_eq!(inventory.len(), 100);
+    assert_eq!(child.list().len(), 100);
+    drop(objs);
+    assert!(inventory.is_empty());
+}
+
+#[test]
+fn test_no_std_concurrent_track_and_list() {
+    let inventory = Inventory::new();
+    let handles: Vec<_> = (0..4u32)
+        .map(|thread_id| {
+            let inventory = inventory.clone();
+            std::thread::spawn(move || {
+                for i in 0..1_000 {
+                    let obj = inventory.track(thread_id * 1_000 + i);
+                    assert!(inventory.list().iter().any(|other| other.ptr_eq(&obj)));
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    assert!(inventory.is_empty());
+    assert_eq!(inventory.total_tracked(), 4_000);
+}
@@ -0,0 +1,53 @@
+//! Counts the allocations of the slab storage.
+//!
+//! The allocations are counted by a global allocator, which lives in its
+//! own test crate so that it does not apply to the other tests.
+
+extern crate census;
+
+use census::{Inventory, TrackedObject};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts the allocations made by each thread.
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the number of allocations made by the current thread
+/// to track and drop `n` objects.
+fn allocations_to_track_and_drop(census: &Inventory<()>, n: usize) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    let objs: Vec<TrackedObject<()>> = (0..n).map(|_| census.track(())).collect();
+    drop(objs);
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn test_slab_storage_allocations() {
+    const N: usize = 1_000_000;
+    let heap_allocations = allocations_to_track_and_drop(&Inventory::new(), N);
+    let slab_census = Inventory::builder().slab(true).build();
+    let slab_allocations = allocations_to_track_and_drop(&slab_census, N);
+    assert!(heap_allocations >= N);
+    assert!(slab_allocations < 100, "{} allocations", slab_allocations);
+    assert!(slab_census.is_empty());
+    // The pages of the slab are reused.
+    assert!(allocations_to_track_and_drop(&slab_census, N) < 10);
+}